@@ -1,30 +1,46 @@
 use std::io::{self, Write};
-use ratatui::Viewport;
-use serde::forward_to_deserialize_any;
+use chrono::NaiveDate;
+use chrono_tz::Tz;
 use itertools::Itertools;
-use crate::habit::{Habit, HabitFunctions};
-use crate::stats::{StreakStats};
-use crate::storage::{save_habits, load_habits};
-use crate::validation::{is_valid_habit_name, find_habit_by_name};
+use crate::clock::Clock;
+use crate::config::Vacation;
+use crate::habit::{Annotation, CompletionRule, Excuse, Habit, HabitFunctions, Target};
+use crate::stats::{AbstinenceStats, LimitStats, QuantityStats, StreakStats};
+use crate::schedule::Schedule;
+use crate::storage::{save_habits, load_habits, save_undo, load_undo, save_config, load_config};
+use crate::units::{self, Amount};
+use crate::validation::{is_valid_habit_name, find_habit};
 
-pub fn run(){
+pub fn run(clock: &dyn Clock){
     println!("🦀 Habit Tracker CLI\n");
-    let mut habits: Vec<Habit> = match load_habits() {
-        Ok(h) => h,
-        Err(_) => vec![],
-    };
+    let mut config = load_config().unwrap_or_default();
+    clock.set_home(config.home());
+    let mut habits: Vec<Habit> = load_habits(clock).unwrap_or_default();
+    let mut undo = load_undo(&habits, clock).unwrap_or_default();
+    let mut day = clock.today();
     loop{
         print!(">");
         io::stdout().flush().unwrap();
         let mut input = String::new();
         io::stdin().read_line(&mut input).unwrap();
+        // A session can run past the end of the day it started on
+        if clock.today() != day {
+            day = clock.today();
+            for habit in habits.iter_mut() {
+                habit.roll_over(day);
+                habit.refresh_streak(day);
+            }
+        }
         let input = input.trim();        
-        let parts: Vec<&str> = input.split_whitespace().collect();
-        let command = *parts.get(0).unwrap_or(&"");
+        let words = split_args(input);
+        let parts: Vec<&str> = words.iter().map(String::as_str).collect();
+        let command = *parts.first().unwrap_or(&"");
         let args = if parts.len()>1{&parts[1..]} else {&[]};
+        // Taken before the command runs so it can be undone
+        let before = if changes_habits(command, args) { habits.clone() } else { Vec::new() };
         match command {
             "quit" | "q" => {
-                match save_habits(&habits){
+                match save_habits(&habits).and_then(|_| save_undo(&undo)){
                     Ok(_) => {println!("auto saving progress, 👋 Goodbye!"); break},
                     Err(_) => {println!("👋 Goodbye!"); break}
                 }
@@ -33,17 +49,51 @@ pub fn run(){
             "help" | "h" => {
                 println!("\n📋 Available Commands:");
                 println!("  add <name>      - Add a new habit");
-                println!("  track <name> <unit> <unit_size>    - Add a new metric to track\n");
-                println!("  list            - Show all habits");
+                println!("  track <name> <unit> <unit_size>    - Add a new metric to track");
+                println!("  limit <name> <unit> <unit_size> <max>    - Add something to do less of");
+                println!("  abstain <name> [YYYY-MM-DD]    - Count days clean since a date (default today)");
+                println!("  timed <name>    - Add a habit measured in minutes");
+                println!("  checklist <name> <item,item,...>    - Add a habit made of steps\n");
+                println!("  list [--tag <tag>]  - Show all habits, or those with a tag");
+                println!("  list --archived - Show archived habits");
                 println!("  save            - Saves progress");
                 println!("  view <name>     - Show specific habit");
+                println!("  search <text>   - Find notes across all habits");
                 println!("  complete <name> - Increment habit streak");
-                println!("  log <name> <quantity>    - Log quantity\n");
-                println!("  reset <name>    - Reset habit to 0");
-                println!("  delete <name>   - Remove habit");
-                println!("  stats           - Show statistics");
+                println!("      add -m \"a note\" and/or -r <1-5> to complete, log, check or stop");
+                println!("  complete <name> --date <YYYY-MM-DD> - Complete a past day");
+                println!("  schedule <name> <when> - daily, mon,wed,fri, 3/week or every-2-days");
+                println!("  log <name> <quantity>    - Log quantity, optionally with a unit (0.5l, 12oz)");
+                println!("      a negative quantity (-250ml) takes some back");
+                println!("  entries <name>  - List today's logged amounts");
+                println!("  entries <name> delete <n> | edit <n> <quantity> - Fix one of them");
+                println!("  relapse <name> [note]    - Restart an abstinence habit's clean days");
+                println!("  check <name> <item> [--date <YYYY-MM-DD>]   - Tick off a checklist item");
+                println!("  uncheck <name> <item> [--date <YYYY-MM-DD>] - Untick a checklist item");
+                println!("  rule <name> <all|any|N>  - Items needed for a checklist to count");
+                println!("  skip <name> [--date <YYYY-MM-DD>]   - Excuse a day so it can't break the streak");
+                println!("  freeze <name> [--date <YYYY-MM-DD>] - Spend a freeze token on a missed day");
+                println!("  vacation [<from> <to> | clear]      - Pause every habit for a range of days");
+                println!("  daystart <hour> - Hour (0-23) a new day begins, for late nights");
+                println!("  timezone <Area/City|local> - Timezone your days are counted in");
+                println!("  start <name>    - Start a timed habit's timer");
+                println!("  stop <name>     - Stop the timer and log the session");
+                println!("  target <name> <goal>     - Daily goal: >=2000, <=3, 1500-2500 or none");
+                println!("  rename <name> <new-name> - Change a habit's name, keeping its history");
+                println!("  edit <name> <unit|unit_size> <value> - Change how a quantity is logged");
+                println!("  edit <name> display <unit|none> - Show a quantity in another unit");
+                println!("  tag <name> <tag>...      - Group a habit, e.g. health, work, learning");
+                println!("  untag <name> <tag>...    - Remove tags from a habit\n");
+                println!("  reset <name>    - Clear today's progress");
+                println!("  archive <name>  - Hide a habit but keep its history (also: delete)");
+                println!("  restore <name>  - Bring an archived habit back");
+                println!("  purge <name>    - Permanently delete a habit and its history");
+                println!("  undo            - Revert the last change");
+                println!("  redo            - Re-apply an undone change");
+                println!("  stats [--tag <tag>] - Show statistics");
                 println!("  save            - Save to file");
-                println!("  quit            - Exit\n");
+                println!("  quit            - Exit");
+                println!("  Any <name> can also be the habit's ID, shown by view\n");
             }
             
             "" => continue,
@@ -52,49 +102,309 @@ pub fn run(){
                     println!("To use: track <name> <unit> <unit_size>");
                     continue;
                 }
+                if !is_valid_habit_name(args[0]) {
+                    println!("Habits should be kebab-case");
+                    continue;
+                }
+                if find_habit(args[0], &habits).is_some() {
+                    println!("❌ Habit '{}' already exists!", args[0]);
+                    continue;
+                }
                 let name = args[0].to_string();
                 let unit = args[1].to_string();
-                let unit_size: u32 = match args[2].parse() {
-                    Ok(n) => n,
-                    Err(_) => {
+                let unit_size: f64 = match args[2].parse::<f64>() {
+                    Ok(n) if n.is_finite() && n > 0.0 => n,
+                    _ => {
                         println!("unit_size must be a number");
                         continue;
                     }
                 };
 
-                habits.push(Habit::new_quantity(name, unit, unit_size));
+                habits.push(Habit::new_quantity(name, unit, unit_size, clock).on_vacations(&config.vacations, clock.today()));
+                undo.record(input, before);
                 println!("📊 Quantity habit added!");
             }
+            "limit" =>{
+                if args.len() != 4 {
+                    println!("To use: limit <name> <unit> <unit_size> <max>");
+                    continue;
+                }
+                let name = args[0];
+                if !is_valid_habit_name(name) {
+                    println!("Habits should be kebab-case");
+                    continue;
+                }
+                if find_habit(name, &habits).is_some() {
+                    println!("❌ Habit '{}' already exists!", name);
+                    continue;
+                }
+                let unit_size = args[2].parse::<f64>().ok().filter(|n| n.is_finite() && *n > 0.0);
+                let max = args[3].parse::<f64>().ok().filter(|n| n.is_finite() && *n >= 0.0);
+                let (Some(unit_size), Some(max)) = (unit_size, max) else {
+                    println!("unit_size must be a number above 0 and max a number of at least 0");
+                    continue;
+                };
+                habits.push(Habit::new_limit(name.to_string(), args[1].to_string(), unit_size, max, clock).on_vacations(&config.vacations, clock.today()));
+                undo.record(input, before);
+                println!("🚫 Limit habit added!");
+            }
+            "checklist" =>{
+                if args.len() != 2 {
+                    println!("To use: checklist <name> <item,item,...>");
+                    continue;
+                }
+                let name = args[0];
+                if !is_valid_habit_name(name) {
+                    println!("Habits should be kebab-case");
+                    continue;
+                }
+                if find_habit(name, &habits).is_some() {
+                    println!("❌ Habit '{}' already exists!", name);
+                    continue;
+                }
+                let items: Vec<String> = args[1].split(',').map(str::to_string).collect();
+                if items.iter().any(|i| i.is_empty()) || items.iter().unique().count() != items.len() {
+                    println!("❌ Items must be distinct and not empty");
+                    continue;
+                }
+                habits.push(Habit::new_checklist(name.to_string(), items, clock).on_vacations(&config.vacations, clock.today()));
+                undo.record(input, before);
+                println!("📝 Checklist habit added!");
+            }
+            "check" | "uncheck" =>{
+                let (args, date, annotation) = match take_date_flag(args)
+                    .and_then(|(args, date)| take_annotation(&args).map(|(args, a)| (args, date, a)))
+                {
+                    Ok(split) => split,
+                    Err(e) => {
+                        println!("❌ {}", e);
+                        continue;
+                    }
+                };
+                if args.len() != 2 {
+                    println!("❌ Usage: {} <habit-name> <item> [--date YYYY-MM-DD]", command);
+                    continue;
+                }
+                match find_habit(args[0], &habits) {
+                    Some(index) => match habits[index].check_item(args[1], date, command == "check", annotation, clock) {
+                        Ok(msg) => {
+                            undo.record(input, before);
+                            println!("✅ {}", msg)
+                        },
+                        Err(e) => println!("❌ {}", e),
+                    },
+                    None => println!("❌ Habit '{}' not found", args[0]),
+                }
+            }
+            "rule" =>{
+                if args.len() != 2 {
+                    println!("❌ Usage: rule <habit-name> <all|any|N>");
+                    continue;
+                }
+                let rule = match CompletionRule::parse(args[1]) {
+                    Ok(rule) => rule,
+                    Err(e) => {
+                        println!("❌ {}", e);
+                        continue;
+                    }
+                };
+                match find_habit(args[0], &habits) {
+                    Some(index) => match habits[index].set_rule(rule, clock) {
+                        Ok(msg) => {
+                            undo.record(input, before);
+                            println!("📝 {}", msg)
+                        },
+                        Err(e) => println!("❌ {}", e),
+                    },
+                    None => println!("❌ Habit '{}' not found", args[0]),
+                }
+            }
+            "timed" =>{
+                if args.len() != 1 {
+                    println!("To use: timed <name>");
+                    continue;
+                }
+                let name = args[0];
+                if !is_valid_habit_name(name) {
+                    println!("Habits should be kebab-case");
+                    continue;
+                }
+                if find_habit(name, &habits).is_some() {
+                    println!("❌ Habit '{}' already exists!", name);
+                    continue;
+                }
+                habits.push(Habit::new_timed(name.to_string(), clock).on_vacations(&config.vacations, clock.today()));
+                undo.record(input, before);
+                println!("⏱️  Timed habit added!");
+            }
+            "start" | "stop" =>{
+                let (args, annotation) = match take_annotation(args) {
+                    Ok(split) => split,
+                    Err(e) => {
+                        println!("❌ {}", e);
+                        continue;
+                    }
+                };
+                if args.len() != 1 {
+                    println!("❌ Usage: {} <habit-name>", command);
+                    continue;
+                }
+                match find_habit(args[0], &habits) {
+                    Some(index) => {
+                        let result = if command == "start" {
+                            habits[index].start_timer(clock)
+                        } else {
+                            habits[index].stop_timer(annotation, clock)
+                        };
+                        match result {
+                            Ok(msg) => {
+                                undo.record(input, before);
+                                println!("⏱️  {}", msg)
+                            },
+                            Err(e) => println!("❌ {}", e),
+                        }
+                    }
+                    None => println!("❌ Habit '{}' not found", args[0]),
+                }
+            }
+            "abstain" =>{
+                if args.is_empty() || args.len() > 2 {
+                    println!("To use: abstain <name> [YYYY-MM-DD]");
+                    continue;
+                }
+                let name = args[0];
+                if !is_valid_habit_name(name) {
+                    println!("Habits should be kebab-case");
+                    continue;
+                }
+                if find_habit(name, &habits).is_some() {
+                    println!("❌ Habit '{}' already exists!", name);
+                    continue;
+                }
+                let started = match args.get(1) {
+                    Some(date) => match NaiveDate::parse_from_str(date, "%Y-%m-%d") {
+                        Ok(date) if date > clock.today() => {
+                            println!("❌ {} hasn't happened yet, start on today or a day gone by", date);
+                            continue;
+                        }
+                        Ok(date) => date,
+                        Err(_) => {
+                            println!("❌ '{}' is not a date, use YYYY-MM-DD", date);
+                            continue;
+                        }
+                    },
+                    None => clock.today(),
+                };
+                habits.push(Habit::new_abstinence(name.to_string(), started, clock).on_vacations(&config.vacations, clock.today()));
+                undo.record(input, before);
+                println!("🌱 Abstinence habit added!");
+            }
+            "relapse" =>{
+                if args.is_empty() {
+                    println!("❌ Usage: relapse <habit-name> [note]");
+                    continue;
+                }
+                let note = if args.len() > 1 { Some(args[1..].join(" ")) } else { None };
+                match find_habit(args[0], &habits) {
+                    Some(index) => match habits[index].relapse(note, clock) {
+                        Ok(msg) => {
+                            undo.record(input, before);
+                            println!("🔁 {}", msg)
+                        },
+                        Err(e) => println!("❌ {}", e),
+                    },
+                    None => println!("❌ Habit '{}' not found", args[0]),
+                }
+            }
             "log" =>{
-                if args.len()!= 2{
-                    println!("To use: log <name> <quantity> ")
+                let (args, annotation) = match take_annotation(args) {
+                    Ok(split) => split,
+                    Err(e) => {
+                        println!("❌ {}", e);
+                        continue;
+                    }
+                };
+                // The unit may be its own word: log water 1.5 l
+                if args.len() != 2 && args.len() != 3 {
+                    println!("To use: log <name> <quantity>[unit] [-m \"note\"] [-r 1-5]");
+                    continue;
                 }
                 let name: String = args[0].to_string();
-                let quantity: u32 = match args[1].parse(){
-                    Ok(n) => n,
-                    Err(_) => {
-                        println!("quantity must be a number");
+                let quantity = match Amount::parse(&args[1..].concat()){
+                    Ok(amount) => amount,
+                    Err(e) => {
+                        println!("❌ {}", e);
                         continue;
                     },
                 };
-                if let Some(index) = find_habit_by_name(&name, &habits) {
-                    match habits[index].log_amount(quantity){
-                        Ok(msg) => println!("✅ {}", msg),
+                if let Some(index) = find_habit(&name, &habits) {
+                    match habits[index].log_amount(quantity, annotation, clock){
+                        Ok(msg) => {
+                            undo.record(input, before);
+                            println!("✅ {}", msg)
+                        },
                         Err(e) => println!("❌ {}", e),                   
                     }
                 }                
 
             }
             "list" | "l" => {
+                let archived = args.contains(&"--archived");
+                let args: Vec<&str> = args.iter().copied().filter(|a| *a != "--archived").collect();
+                let tag = match take_flag(&args, &["--tag", "-t"]) {
+                    Ok((_, tag)) => tag,
+                    Err(e) => {
+                        println!("❌ {}", e);
+                        continue;
+                    }
+                };
+                let shown: Vec<(usize, &Habit)> = habits.iter().enumerate()
+                    .filter(|(_, h)| h.is_shown(tag, archived))
+                    .collect();
                 if habits.is_empty() {
                     println!("No habits yet!");
+                } else if shown.is_empty() {
+                    match (tag, archived) {
+                        (Some(tag), true) => println!("No archived habits tagged {}", tag),
+                        (None, true) => println!("No archived habits"),
+                        (Some(tag), false) => println!("No habits tagged {}", tag),
+                        (None, false) => println!("All habits are archived, see list --archived"),
+                    }
                 } else {
-                    println!("\nYour habits:");
-                    for (i, habit) in habits.iter().enumerate() {
-                        println!("  {}. {}", i + 1, habit.display_line());
+                    match (tag, archived) {
+                        (Some(tag), true) => println!("\nArchived {} habits:", tag),
+                        (None, true) => println!("\nArchived habits:"),
+                        (Some(tag), false) => println!("\nYour {} habits:", tag),
+                        (None, false) => println!("\nYour habits:"),
+                    }
+                    for (i, habit) in shown {
+                        println!("  {}. {}", i + 1, habit.display_line(clock));
                     }
                 }
             }
+            "tag" | "untag" =>{
+                if args.len() < 2 {
+                    println!("❌ Usage: {} <habit-name> <tag>...", command);
+                    continue;
+                }
+                match find_habit(args[0], &habits) {
+                    Some(index) => {
+                        let result = if command == "tag" {
+                            habits[index].tag(&args[1..])
+                        } else {
+                            habits[index].untag(&args[1..])
+                        };
+                        match result {
+                            Ok(msg) => {
+                                undo.record(input, before);
+                                println!("🏷️  {}", msg)
+                            },
+                            Err(e) => println!("❌ {}", e),
+                        }
+                    },
+                    None => println!("❌ Habit '{}' not found", args[0]),
+                }
+            }
             "add" | "a" => {
                 
                 if args.is_empty(){println!("To use add, try: add <habit-name>")}
@@ -105,45 +415,336 @@ pub fn run(){
                 else{
                     let habit_name = args[0]; 
 
-                    if !is_valid_habit_name(&habit_name){println!("Habits should be kebab-case")}
+                    if !is_valid_habit_name(habit_name){println!("Habits should be kebab-case")}
                     
-                    else if find_habit_by_name(habit_name, &habits).is_some() {
+                    else if find_habit(habit_name, &habits).is_some() {
                         println!("❌ Habit '{}' already exists!", habit_name);
                     }
                     else{
-                        habits.push(Habit::new_streak(habit_name.to_string()));
+                        habits.push(Habit::new_streak(habit_name.to_string(), clock).on_vacations(&config.vacations, clock.today()));
+                        undo.record(input, before);
                         println!("Habit {} successfully added", habit_name)
                     }
                 }
             }
             "save" | "s" =>{
-                match save_habits(&habits){
+                match save_habits(&habits).and_then(|_| save_undo(&undo)){
                     Ok(_) => {println!("Saved progress")},
                     Err(e) => {println!("Error saving to file: {}",e)}
                 }
             }
             "complete" | "c" =>{
-                if args.is_empty(){println!("❌ Usage: complete <habit-name>")}
+                let (args, date, annotation) = match take_date_flag(args)
+                    .and_then(|(args, date)| take_annotation(&args).map(|(args, a)| (args, date, a)))
+                {
+                    Ok(split) => split,
+                    Err(e) => {
+                        println!("❌ {}", e);
+                        continue;
+                    }
+                };
+                if args.is_empty(){println!("❌ Usage: complete <habit-name> [--date YYYY-MM-DD] [-m \"note\"] [-r 1-5]")}
                 else if args.len()>1{
                     println!("❌ Habit name cannot contain spaces");
                     println!("   Did you mean: {}?", args.join("-"));
                 }
                 else{
                     let habit_name = args[0];
-                    // match find_habit_by_name(habit_name, &habits){
-                    //     Some(index) =>{
-                    //         habits[index].complete();
-                    //         let new_streak = habits[index].streak;
-                    //         println!("Great job! You upped your streak from {} to {}", new_streak-1, new_streak);
-                    //     }
-                    //     None => println!("Habit name {} not found", habit_name)
-                    // }
-                    if let Some(index) = find_habit_by_name(habit_name, &habits) {
-                        match habits[index].complete() {
-                            Ok(msg) => println!("✅ {}", msg),
+                    if let Some(index) = find_habit(habit_name, &habits) {
+                        let result = match date {
+                            Some(date) => habits[index].complete_on(date, annotation, clock),
+                            None => habits[index].complete(annotation, clock),
+                        };
+                        match result {
+                            Ok(msg) => {
+                                undo.record(input, before);
+                                println!("✅ {}", msg)
+                            },
+                            Err(e) => println!("❌ {}", e),
+                        }
+                    }
+                }
+            }
+            "skip" | "freeze" =>{
+                let (args, date) = match take_date_flag(args) {
+                    Ok(split) => split,
+                    Err(e) => {
+                        println!("❌ {}", e);
+                        continue;
+                    }
+                };
+                if args.len() != 1 {
+                    println!("❌ Usage: {} <habit-name> [--date YYYY-MM-DD]", command);
+                    continue;
+                }
+                let excuse = if command == "skip" { Excuse::Skip } else { Excuse::Freeze };
+                let date = date.unwrap_or_else(|| clock.today());
+                match find_habit(args[0], &habits) {
+                    Some(index) => match habits[index].excuse(date, excuse, clock) {
+                        Ok(msg) => {
+                            undo.record(input, before);
+                            println!("⏸️  {}", msg)
+                        },
+                        Err(e) => println!("❌ {}", e),
+                    },
+                    None => println!("❌ Habit '{}' not found", args[0]),
+                }
+            }
+            "vacation" =>{
+                let today = clock.today();
+                match args {
+                    [] => {
+                        if config.vacations.is_empty() {
+                            println!("No vacations planned");
+                        }
+                        for v in &config.vacations {
+                            println!("  🏖 {} to {}", v.from, v.to);
+                        }
+                        continue;
+                    }
+                    ["clear"] => {
+                        config.vacations.clear();
+                        println!("🏖 Vacations cleared");
+                    }
+                    [from, to] => {
+                        let (Ok(from), Ok(to)) = (
+                            NaiveDate::parse_from_str(from, "%Y-%m-%d"),
+                            NaiveDate::parse_from_str(to, "%Y-%m-%d"),
+                        ) else {
+                            println!("❌ Dates must look like YYYY-MM-DD");
+                            continue;
+                        };
+                        if from > to {
+                            println!("❌ Vacation ends before it starts");
+                            continue;
+                        }
+                        config.vacations.push(Vacation { from, to });
+                        println!("🏖 All habits paused from {} to {}", from, to);
+                    }
+                    _ => {
+                        println!("❌ Usage: vacation [<from> <to> | clear]");
+                        continue;
+                    }
+                }
+                for habit in habits.iter_mut() {
+                    habit.apply_vacations(&config.vacations, today);
+                }
+                if let Err(e) = save_config(&config) {
+                    println!("Error saving settings: {}", e);
+                }
+            }
+            "daystart" =>{
+                let hour = match args {
+                    [hour] => hour.parse::<u32>().ok().filter(|h| *h < 24),
+                    _ => None,
+                };
+                let Some(hour) = hour else {
+                    println!("❌ Usage: daystart <0-23>");
+                    continue;
+                };
+                config.day_starts_at = hour;
+                clock.set_home(config.home());
+                let today = clock.today();
+                for habit in habits.iter_mut() {
+                    habit.refresh_streak(today);
+                }
+                match save_config(&config) {
+                    Ok(_) => println!("🌙 Days now start at {:02}:00", hour),
+                    Err(e) => println!("Error saving settings: {}", e),
+                }
+            }
+            "timezone" =>{
+                let timezone = match args {
+                    [] => {
+                        match config.home_timezone {
+                            Some(tz) => println!("🌍 Days are counted in {}", tz),
+                            None => println!("🌍 Days are counted in this computer's timezone"),
+                        }
+                        continue;
+                    }
+                    [name] if *name == "local" => None,
+                    [name] => match name.parse::<Tz>() {
+                        Ok(tz) => Some(tz),
+                        Err(_) => {
+                            println!("❌ Unknown timezone '{}'. Try e.g. Europe/Berlin or local", name);
+                            continue;
+                        }
+                    },
+                    _ => {
+                        println!("❌ Usage: timezone <Area/City|local>");
+                        continue;
+                    }
+                };
+                config.home_timezone = timezone;
+                clock.set_home(config.home());
+                let today = clock.today();
+                for habit in habits.iter_mut() {
+                    habit.refresh_streak(today);
+                }
+                match save_config(&config) {
+                    Ok(_) => println!("🌍 Days are now counted in {}", timezone.map_or("local time".to_string(), |tz| tz.to_string())),
+                    Err(e) => println!("Error saving settings: {}", e),
+                }
+            }
+            "search" =>{
+                if args.is_empty() {
+                    println!("❌ Usage: search <text>");
+                    continue;
+                }
+                let text = args.join(" ");
+                let mut found = 0;
+                for habit in &habits {
+                    for (date, annotation) in habit.annotations(clock.home()) {
+                        if annotation.matches(&text) {
+                            println!("  {} {}: {}", date, habit.name(), annotation);
+                            found += 1;
+                        }
+                    }
+                }
+                if found == 0 {
+                    println!("No notes mention '{}'", text);
+                }
+            }
+            "schedule" =>{
+                if args.len() != 2 {
+                    println!("❌ Usage: schedule <habit-name> <daily|mon,wed,fri|3/week|every-2-days>");
+                    continue;
+                }
+                let schedule = match Schedule::parse(args[1]) {
+                    Ok(schedule) => schedule,
+                    Err(e) => {
+                        println!("❌ {}", e);
+                        continue;
+                    }
+                };
+                match find_habit(args[0], &habits) {
+                    Some(index) => match habits[index].set_schedule(schedule, clock) {
+                        Ok(msg) => {
+                            undo.record(input, before);
+                            println!("📅 {}", msg)
+                        },
+                        Err(e) => println!("❌ {}", e),
+                    },
+                    None => println!("❌ Habit '{}' not found", args[0]),
+                }
+            }
+            "target" =>{
+                if args.len() != 2 {
+                    println!("❌ Usage: target <habit-name> <>=N|<=N|LOW-HIGH|none>");
+                    continue;
+                }
+                let target = if args[1] == "none" {
+                    None
+                } else {
+                    match Target::parse(args[1]) {
+                        Ok(target) => Some(target),
+                        Err(e) => {
+                            println!("❌ {}", e);
+                            continue;
+                        }
+                    }
+                };
+                match find_habit(args[0], &habits) {
+                    Some(index) => match habits[index].set_target(target) {
+                        Ok(msg) => {
+                            undo.record(input, before);
+                            println!("🎯 {}", msg)
+                        },
+                        Err(e) => println!("❌ {}", e),
+                    },
+                    None => println!("❌ Habit '{}' not found", args[0]),
+                }
+            }
+            "rename" =>{
+                if args.len() != 2 {
+                    println!("❌ Usage: rename <habit-name> <new-name>");
+                    continue;
+                }
+                if find_habit(args[1], &habits).is_some() {
+                    println!("❌ Habit '{}' already exists!", args[1]);
+                    continue;
+                }
+                match find_habit(args[0], &habits) {
+                    Some(index) => match habits[index].rename(args[1]) {
+                        Ok(msg) => {
+                            undo.record(input, before);
+                            println!("✏️  {}", msg)
+                        },
+                        Err(e) => println!("❌ {}", e),
+                    },
+                    None => println!("❌ Habit '{}' not found", args[0]),
+                }
+            }
+            "edit" =>{
+                if args.len() != 3 {
+                    println!("❌ Usage: edit <habit-name> <unit|unit_size|display> <value>");
+                    continue;
+                }
+                let Some(index) = find_habit(args[0], &habits) else {
+                    println!("❌ Habit '{}' not found", args[0]);
+                    continue;
+                };
+                let convertible = habits[index].unit().is_none_or(|unit| units::convert(1.0, unit, args[2]).is_ok());
+                let result = if args[1] == "unit" && !convertible {
+                    // Keeping the numbers changes what every past amount means
+                    println!("⚠️  {} can't be converted to {}, so past amounts would keep their numbers.",
+                        habits[index].unit().unwrap_or_default(), args[2]);
+                    print!("Type the new unit again to relabel anyway: ");
+                    io::stdout().flush().unwrap();
+                    let mut confirm = String::new();
+                    io::stdin().read_line(&mut confirm).unwrap();
+                    if confirm.trim() != args[2] {
+                        println!("Edit cancelled");
+                        continue;
+                    }
+                    habits[index].relabel_unit(args[2])
+                } else {
+                    habits[index].edit(args[1], args[2])
+                };
+                match result {
+                    Ok(msg) => {
+                        undo.record(input, before);
+                        println!("✏️  {}", msg)
+                    },
+                    Err(e) => println!("❌ {}", e),
+                }
+            }
+            "entries" =>{
+                let Some(index) = args.first().and_then(|name| find_habit(name, &habits)) else {
+                    println!("❌ Usage: entries <habit-name> [delete <n> | edit <n> <quantity>]");
+                    continue;
+                };
+                let number = |n: &str| n.parse::<usize>().map_err(|_| format!("'{}' is not an entry number", n));
+                let result = match args[1..] {
+                    [] => {
+                        match habits[index].entries(clock) {
+                            Ok(entries) if entries.is_empty() => println!("Nothing logged for {} today", habits[index].name()),
+                            Ok(entries) => {
+                                println!("\n{} today:", habits[index].name());
+                                for entry in entries {
+                                    println!("  {}", entry);
+                                }
+                            }
                             Err(e) => println!("❌ {}", e),
                         }
+                        continue;
+                    }
+                    ["delete", n] => number(n).and_then(|n| habits[index].delete_entry(n, clock)),
+                    ["edit", n, ref amount @ ..] if !amount.is_empty() && amount.len() <= 2 => {
+                        number(n).and_then(|n| Ok((n, Amount::parse(&amount.concat())?)))
+                            .and_then(|(n, amount)| habits[index].edit_entry(n, amount, clock))
+                    }
+                    _ => {
+                        println!("❌ Usage: entries <habit-name> [delete <n> | edit <n> <quantity>]");
+                        continue;
                     }
+                };
+                match result {
+                    Ok(msg) => {
+                        undo.record(input, before);
+                        println!("✏️  {}", msg)
+                    },
+                    Err(e) => println!("❌ {}", e),
                 }
             }
             "view" | "v" =>{
@@ -154,9 +755,11 @@ pub fn run(){
                 }
                 else{
                     let habit_name = args[0];
-                    if let Some(index) = find_habit_by_name(habit_name, &habits) {
-                        println!("{}", habits[index].display_line());
-
+                    if let Some(index) = find_habit(habit_name, &habits) {
+                        println!("{}", habits[index].display_line(clock));
+                        for line in habits[index].details(clock) {
+                            println!("  {}", line);
+                        }
                     }
                 } 
             }
@@ -168,47 +771,123 @@ pub fn run(){
                 }
                 else{
                     let habit_name = args[0];
-                    match find_habit_by_name(habit_name, &habits){
-                        Some(index) => {habits[index].reset();println!("Reset exercise {}", habits[index].name());}
+                    match find_habit(habit_name, &habits){
+                        Some(index) => {
+                            match habits[index].reset(clock) {
+                                Ok(msg) => {
+                                    undo.record(input, before);
+                                    println!("{}", msg);
+                                }
+                                Err(e) => println!("❌ {}", e),
+                            }
+                        }
                         None => println!("Habit not {} found", habit_name)
                     }
                 }
             }
-            "delete" | "d" => {
-                if args.is_empty() {
-                    println!("❌ Usage: delete <habit-name>");
-                } 
-                else if args.len()>1{
-                    println!("❌ Habit name cannot contain spaces");
-                    println!("   Did you mean: {}?", args.join("-"));
+            "archive" | "delete" | "d" | "restore" => {
+                if args.len() != 1 {
+                    println!("❌ Usage: {} <habit-name>", command);
+                    continue;
                 }
-                else {
-                    let habit_name = args[0];
-                    
-                    match find_habit_by_name(habit_name, &habits) {
-                        Some(index) => {
-                            habits.remove(index);  // Vec method, not Habit method!
-                            println!("🗑️  Deleted: {}", habit_name);
+                match find_habit(args[0], &habits) {
+                    Some(index) => {
+                        let result = if command == "restore" {
+                            habits[index].restore(clock)
+                        } else {
+                            habits[index].archive(clock)
+                        };
+                        match result {
+                            Ok(msg) => {
+                                undo.record(input, before);
+                                println!("📦 {}", msg)
+                            },
+                            Err(e) => println!("❌ {}", e),
                         }
-                        None => {
-                            println!("❌ Habit '{}' not found", habit_name);
+                    },
+                    None => println!("❌ Habit '{}' not found", args[0]),
+                }
+            }
+            "purge" => {
+                if args.len() != 1 {
+                    println!("❌ Usage: purge <habit-name>");
+                    continue;
+                }
+                let habit_name = args[0];
+                let Some(index) = find_habit(habit_name, &habits) else {
+                    println!("❌ Habit '{}' not found", habit_name);
+                    continue;
+                };
+                println!("⚠️  This deletes {} and all of its history for good.", habit_name);
+                print!("Type the name again to confirm: ");
+                io::stdout().flush().unwrap();
+                let mut confirm = String::new();
+                io::stdin().read_line(&mut confirm).unwrap();
+                if confirm.trim() == habit_name {
+                    // Not an undo step, and gone from the ones before it
+                    let purged = habits.remove(index);
+                    undo.forget(purged.id());
+                    println!("🗑️  Purged: {}", habit_name);
+                } else {
+                    println!("Purge cancelled");
+                }
+            }
+            "undo" | "u" => {
+                match undo.undo(&mut habits, clock.today(), &config.vacations) {
+                    Some(label) => println!("↩️  Undid: {}", label),
+                    None => println!("Nothing to undo"),
+                }
+            }
+            "redo" => {
+                match undo.redo(&mut habits, clock.today(), &config.vacations) {
+                    Some(label) => println!("↪️  Redid: {}", label),
+                    None => println!("Nothing to redo"),
+                }
+            }
+            "stats" => {
+                let tag = match take_flag(args, &["--tag", "-t"]) {
+                    Ok((_, tag)) => tag,
+                    Err(e) => {
+                        println!("❌ {}", e);
+                        continue;
+                    }
+                };
+                let habits: Vec<Habit> = habits.iter()
+                    .filter(|h| h.is_shown(tag, false))
+                    .cloned()
+                    .collect();
+                if habits.is_empty() {
+                    println!("📊 No habits to show stats for!");
+                } else {
+                    let stats = StreakStats::calculate(&habits, clock.today());
+                    println!("\n📊 Habit Statistics");
+                    println!("━━━━━━━━━━━━━━━━━━━━");
+                    println!("Habits with a streak: {}", stats.total);
+                    println!("Active (streak > 0): {}", stats.active);
+                    println!("Longest streak: {} days", stats.longest);
+                    println!("Average streak: {:.1} days\n", stats.average);
+                    let limits = LimitStats::calculate(&habits, clock.today());
+                    if limits.total > 0 {
+                        println!("Limits: {}\n", limits.display_stats());
+                    }
+                    for habit in &habits {
+                        match habit {
+                            Habit::Abstinence(a) => {
+                                let stats = AbstinenceStats::calculate(a, clock.today());
+                                println!("{}: {}", a.name, stats.display_stats());
+                            }
+                            Habit::Quantity(q) => {
+                                let stats = QuantityStats::calculate(q, clock.home());
+                                println!("{}: {}", q.name, stats.display_stats(q));
+                                if !stats.by_hour.is_empty() {
+                                    println!("  today by hour: {}", stats.display_hours(q));
+                                }
+                            }
+                            _ => {}
                         }
                     }
                 }
             }
-            // "stats" => {
-            //     if habits.is_empty() {
-            //         println!("📊 No habits to show stats for!");
-            //     } else {
-            //         let stats = StreakStats::calculate(&habits);
-            //         println!("\n📊 Habit Statistics");
-            //         println!("━━━━━━━━━━━━━━━━━━━━");
-            //         println!("Total habits: {}", stats.total);
-            //         println!("Active (streak > 0): {}", stats.active);
-            //         println!("Longest streak: {} days", stats.longest);
-            //         println!("Average streak: {:.1} days\n", stats.average);
-            //     }
-            // }
             _ => {
                 println!("❌ Unknown command: '{}'", command);
                 println!("💡 Type 'help' to see available commands");
@@ -216,3 +895,88 @@ pub fn run(){
         }
     }
 }
+
+// Whether `command` can record an undo step, which needs a copy of the
+// habits from before it ran. Undo and redo keep their own copies, and
+// settings commands aren't undone
+fn changes_habits(command: &str, args: &[&str]) -> bool{
+    match command {
+        "" | "quit" | "q" | "help" | "h" | "save" | "s" | "list" | "l" | "view" | "v" | "search"
+            | "stats" | "undo" | "u" | "redo" | "vacation" | "daystart" | "timezone" | "purge" => false,
+        // Without more than a name it only lists
+        "entries" => args.len() > 1,
+        _ => true,
+    }
+}
+
+// Splits a command line on whitespace, keeping "quoted text" together
+fn split_args(input: &str) -> Vec<String>{
+    let mut words = Vec::new();
+    let mut word = String::new();
+    let mut quoted = false;
+    for c in input.chars() {
+        match c {
+            '"' => quoted = !quoted,
+            c if c.is_whitespace() && !quoted => {
+                if !word.is_empty() {
+                    words.push(std::mem::take(&mut word));
+                }
+            }
+            c => word.push(c),
+        }
+    }
+    if !word.is_empty() {
+        words.push(word);
+    }
+    words
+}
+
+// Pulls `<flag> <value>` out of the arguments, returning what is left
+fn take_flag<'a>(args: &[&'a str], flags: &[&str]) -> Result<(Vec<&'a str>, Option<&'a str>), String>{
+    let Some(pos) = args.iter().position(|a| flags.contains(a)) else {
+        return Ok((args.to_vec(), None));
+    };
+    let value = *args.get(pos + 1).ok_or(format!("{} needs a value", args[pos]))?;
+    let rest = args.iter().enumerate()
+        .filter(|(i, _)| *i != pos && *i != pos + 1)
+        .map(|(_, a)| *a)
+        .collect();
+    Ok((rest, Some(value)))
+}
+
+// Pulls `--date YYYY-MM-DD` out of the arguments, returning what is left
+fn take_date_flag<'a>(args: &[&'a str]) -> Result<(Vec<&'a str>, Option<NaiveDate>), String>{
+    let (rest, value) = take_flag(args, &["--date"])?;
+    let date = value.map(|value| NaiveDate::parse_from_str(value, "%Y-%m-%d")
+        .map_err(|_| format!("'{}' is not a date, use YYYY-MM-DD", value)))
+        .transpose()?;
+    Ok((rest, date))
+}
+
+// Pulls `-m "note"` and `-r 1-5` out of the arguments
+fn take_annotation<'a>(args: &[&'a str]) -> Result<(Vec<&'a str>, Annotation), String>{
+    let (rest, note) = take_flag(args, &["-m", "--note"])?;
+    let (rest, rating) = take_flag(&rest, &["-r", "--rating"])?;
+    let rating = rating.map(|r| r.parse::<u8>()
+        .map_err(|_| format!("Rating must be 1 to 5, got '{}'", r)))
+        .transpose()?;
+    let annotation = Annotation::new(note.map(str::to_string), rating)?;
+    Ok((rest, annotation))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn notes_and_ratings_are_taken_from_the_arguments(){
+        let (rest, annotation) = take_annotation(&["read", "-m", "good chapter", "-r", "4"]).unwrap();
+        assert_eq!(rest, ["read"]);
+        assert_eq!(annotation, Annotation::new(Some("good chapter".to_string()), Some(4)).unwrap());
+        let (rest, annotation) = take_annotation(&["read"]).unwrap();
+        assert_eq!((rest, annotation.is_empty()), (vec!["read"], true));
+        assert!(take_annotation(&["read", "-r", "9"]).is_err());
+        assert!(take_annotation(&["read", "-r", "high"]).is_err());
+        assert!(take_annotation(&["read", "-m"]).is_err());
+    }
+}
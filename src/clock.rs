@@ -0,0 +1,116 @@
+use std::cell::Cell;
+
+use chrono::{NaiveDate, TimeDelta, Timelike, Utc};
+
+use crate::config::Home;
+use crate::timestamp::Timestamp;
+
+// Where habits get the time from, so days can be simulated and tested
+pub trait Clock{
+    fn now(&self) -> Timestamp;
+    // The timezone and day start hour days are counted with
+    fn home(&self) -> Home;
+    // Follows the config when the day start or timezone is changed
+    fn set_home(&self, home: Home);
+    // The habit day `now` falls in
+    fn today(&self) -> NaiveDate{
+        self.home().day_of(self.now())
+    }
+}
+
+// The computer's clock, to the second
+pub struct SystemClock{
+    home: Cell<Home>,
+}
+
+impl SystemClock{
+    pub fn new(home: Home) -> Self{
+        SystemClock { home: Cell::new(home) }
+    }
+}
+
+impl Clock for SystemClock{
+    fn now(&self) -> Timestamp{
+        let now = self.home().in_home(Utc::now());
+        now.with_nanosecond(0).unwrap_or(now)
+    }
+    fn home(&self) -> Home{
+        self.home.get()
+    }
+    fn set_home(&self, home: Home){
+        self.home.set(home);
+    }
+}
+
+// The real clock moved to another day, for `--today <date>`. Time of day
+// keeps running so timers still work
+pub struct SimulatedClock{
+    pub today: NaiveDate,
+    system: SystemClock,
+}
+
+impl SimulatedClock{
+    pub fn new(today: NaiveDate, home: Home) -> Self{
+        SimulatedClock { today, system: SystemClock::new(home) }
+    }
+}
+
+impl Clock for SimulatedClock{
+    fn now(&self) -> Timestamp{
+        let now = self.system.now();
+        now + TimeDelta::days((self.today - self.home().day_of(now)).num_days())
+    }
+    fn home(&self) -> Home{
+        self.system.home()
+    }
+    fn set_home(&self, home: Home){
+        self.system.set_home(home);
+    }
+}
+
+// A clock that only moves when told to
+#[cfg(test)]
+pub struct FixedClock{
+    now: Cell<Timestamp>,
+    home: Cell<Home>,
+}
+
+#[cfg(test)]
+impl FixedClock{
+    // Starts at a home wall clock time like "2024-02-28 23:59"
+    pub fn at(time: &str) -> Self{
+        FixedClock::at_home(time, Home::default())
+    }
+    // The same, with days counted in `home`
+    pub fn at_home(time: &str, home: Home) -> Self{
+        let naive = chrono::NaiveDateTime::parse_from_str(time, "%Y-%m-%d %H:%M").expect("test time");
+        FixedClock { now: Cell::new(home.at_home_time(naive)), home: Cell::new(home) }
+    }
+    pub fn advance(&self, by: TimeDelta){
+        self.now.set(self.now.get() + by);
+    }
+}
+
+#[cfg(test)]
+impl Clock for FixedClock{
+    fn now(&self) -> Timestamp{
+        self.now.get()
+    }
+    fn home(&self) -> Home{
+        self.home.get()
+    }
+    fn set_home(&self, home: Home){
+        self.home.set(home);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn simulated_clock_reports_the_given_day(){
+        let today = NaiveDate::from_ymd_opt(2024, 2, 29).unwrap();
+        assert_eq!(SimulatedClock::new(today, Home::default()).today(), today);
+    }
+}
@@ -0,0 +1,79 @@
+use chrono::{DateTime, Local, NaiveDate, NaiveDateTime, TimeDelta, TimeZone, Utc};
+use chrono_tz::Tz;
+use serde::{Deserialize, Serialize};
+
+use crate::timestamp::Timestamp;
+
+// A stretch of days that pauses every habit
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+pub struct Vacation{
+    pub from: NaiveDate,
+    pub to: NaiveDate,
+}
+
+impl Vacation{
+    pub fn days(&self) -> impl Iterator<Item = NaiveDate>{
+        let to = self.to;
+        self.from.iter_days().take_while(move |d| *d <= to)
+    }
+}
+
+// Settings that apply to all habits, kept in config.json
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct Config{
+    #[serde(default)]
+    pub vacations: Vec<Vacation>,
+    // 0-23, so night owls can have 00:30 still count as the day before
+    #[serde(default)]
+    pub day_starts_at: u32,
+    // Days are counted in this timezone wherever you happen to be.
+    // None means whatever the computer is set to
+    #[serde(default)]
+    pub home_timezone: Option<Tz>,
+}
+
+impl Config{
+    // The day boundary and timezone, for the clock to count days with
+    pub fn home(&self) -> Home{
+        Home { day_starts_at: self.day_starts_at, timezone: self.home_timezone }
+    }
+}
+
+// Where and when days are counted
+#[derive(Debug, Clone, Copy, Default, PartialEq)]
+pub struct Home{
+    pub day_starts_at: u32,
+    // None for whatever the computer is set to
+    pub timezone: Option<Tz>,
+}
+
+impl Home{
+    // The same moment with the home timezone's offset
+    pub fn in_home(&self, at: DateTime<Utc>) -> Timestamp{
+        match self.timezone {
+            Some(tz) => at.with_timezone(&tz).fixed_offset(),
+            None => at.with_timezone(&Local).fixed_offset(),
+        }
+    }
+
+    // Wall clock time at home for a moment recorded anywhere
+    pub fn home_time(&self, at: Timestamp) -> NaiveDateTime{
+        self.in_home(at.to_utc()).naive_local()
+    }
+
+    // Reads a time without an offset, as older files stored them, as home time
+    pub fn at_home_time(&self, naive: NaiveDateTime) -> Timestamp{
+        let at = match self.timezone {
+            Some(tz) => tz.from_local_datetime(&naive).earliest().map(|t| t.fixed_offset()),
+            None => Local.from_local_datetime(&naive).earliest().map(|t| t.fixed_offset()),
+        };
+        // Only a time skipped by a DST change has no match, an hour later does
+        at.unwrap_or_else(|| self.at_home_time(naive + TimeDelta::hours(1)))
+    }
+
+    // The habit day a moment belongs to: its date at home, with the day
+    // starting at `day_starts_at`
+    pub fn day_of(&self, at: Timestamp) -> NaiveDate{
+        (self.home_time(at) - TimeDelta::hours(self.day_starts_at as i64)).date()
+    }
+}
@@ -1,41 +1,368 @@
-use chrono::{NaiveDate, NaiveTime, NaiveWeek, Timelike, format};
+use std::collections::{BTreeMap, BTreeSet};
+use std::fmt;
+
+use chrono::{NaiveDate, NaiveTime, TimeDelta, Timelike, Weekday};
+use itertools::Itertools;
 use serde::{Deserialize,Serialize};
 
-use crate::validation::find_habit_by_name;
+use crate::clock::Clock;
+use crate::config::{Home, Vacation};
+use crate::schedule::{Run, Schedule, StreakStatus};
+use crate::timestamp::{self, Timestamp};
+use crate::units::{self, Amount};
+use crate::validation::is_valid_habit_name;
+
+// Optional context on a completion or log entry
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct Annotation{
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub note: Option<String>,
+    // 1-5, how it went
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub rating: Option<u8>,
+}
+
+impl Annotation{
+    pub fn new(note: Option<String>, rating: Option<u8>) -> Result<Self, String>{
+        if let Some(r) = rating && !(1..=5).contains(&r) {
+            return Err(format!("Rating must be 1 to 5, got {}", r));
+        }
+        Ok(Annotation { note, rating })
+    }
+    pub fn is_empty(&self) -> bool{
+        self.note.is_none() && self.rating.is_none()
+    }
+    pub fn matches(&self, text: &str) -> bool{
+        self.note.as_ref().is_some_and(|n| n.to_lowercase().contains(&text.to_lowercase()))
+    }
+}
+
+impl fmt::Display for Annotation{
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result{
+        match (&self.note, self.rating) {
+            (Some(note), Some(rating)) => write!(f, "{} ({}/5)", note, rating),
+            (Some(note), None) => write!(f, "{}", note),
+            (None, Some(rating)) => write!(f, "({}/5)", rating),
+            (None, None) => Ok(()),
+        }
+    }
+}
+
+// Why a day doesn't count against a streak
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+pub enum Excuse{
+    // Declared by hand, e.g. a sick day
+    Skip,
+    // Paid for with a token earned by being consistent
+    Freeze,
+    // Part of a global vacation range
+    Vacation,
+}
+
+impl fmt::Display for Excuse{
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result{
+        match self {
+            Excuse::Skip => write!(f, "skipped"),
+            Excuse::Freeze => write!(f, "frozen ❄"),
+            Excuse::Vacation => write!(f, "vacation 🏖"),
+        }
+    }
+}
+
+// One freeze token is earned for every this many successful days
+pub const FREEZE_EVERY: u32 = 7;
+
+// Days that neither break nor extend a streak
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+#[serde(transparent)]
+pub struct Excused(BTreeMap<NaiveDate, Excuse>);
+
+impl Excused{
+    pub fn is_empty(&self) -> bool{
+        self.0.is_empty()
+    }
+    pub fn get(&self, date: NaiveDate) -> Option<Excuse>{
+        self.0.get(&date).copied()
+    }
+    pub fn days(&self) -> BTreeSet<NaiveDate>{
+        self.0.keys().copied().collect()
+    }
+    pub fn iter(&self) -> impl DoubleEndedIterator<Item = (&NaiveDate, &Excuse)>{
+        self.0.iter()
+    }
+    // A skip or freeze is kept over a vacation covering the same day
+    pub fn insert(&mut self, date: NaiveDate, excuse: Excuse){
+        if excuse != Excuse::Vacation || !self.0.contains_key(&date) {
+            self.0.insert(date, excuse);
+        }
+    }
+    pub fn clear_vacations(&mut self){
+        self.0.retain(|_, e| *e != Excuse::Vacation);
+    }
+    pub fn clear(&mut self){
+        self.0.clear();
+    }
+    // Tokens earned from `successes` days that haven't been spent yet
+    pub fn freezes_available(&self, successes: u32) -> u32{
+        let used = self.0.values().filter(|e| **e == Excuse::Freeze).count() as u32;
+        (successes / FREEZE_EVERY).saturating_sub(used)
+    }
+}
+
+// What tells habits apart, whatever they are called
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct Identity{
+    // Empty only in files from before habits had IDs, until they are loaded
+    #[serde(default, skip_serializing_if = "String::is_empty")]
+    pub id: String,
+    #[serde(default, deserialize_with = "timestamp::deserialize_option", skip_serializing_if = "Option::is_none")]
+    pub created: Option<Timestamp>,
+}
+
+impl Identity{
+    pub fn new(created: Timestamp) -> Self{
+        Identity { id: new_id(), created: Some(created) }
+    }
+}
+
+// Where a habit is filed among the others
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct Filing{
+    // Categories like health or work, for filtering and grouping
+    #[serde(default, skip_serializing_if = "BTreeSet::is_empty")]
+    pub tags: BTreeSet<String>,
+    // Day it was put away. Archived habits keep their history but stay
+    // out of lists and stats
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub archived: Option<NaiveDate>,
+}
+
+// Eight hex digits, short enough to type in place of a name
+fn new_id() -> String{
+    use std::hash::{BuildHasher, Hasher};
+    let mut hasher = std::collections::hash_map::RandomState::new().build_hasher();
+    hasher.write_u128(std::time::SystemTime::now()
+        .duration_since(std::time::UNIX_EPOCH)
+        .map_or(0, |d| d.as_nanos()));
+    format!("{:08x}", hasher.finish() as u32)
+}
+
+// A day or a Monday-Sunday week with the amount logged in it
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+pub struct Best{
+    pub date: NaiveDate,
+    pub value: f64,
+}
+
+// Personal bests, kept so they outlive the runs and days they came from
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct Records{
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub streak: Option<Run>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub day: Option<Best>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub week: Option<Best>,
+}
+
+impl Records{
+    pub fn is_empty(&self) -> bool{
+        *self == Records::default()
+    }
+    // Keeps whichever is higher. Returns true when an earlier record was beaten
+    fn update_streak(&mut self, run: Option<Run>) -> bool{
+        match (self.streak, run) {
+            (Some(best), Some(run)) if run.count > best.count => {
+                self.streak = Some(run);
+                true
+            }
+            (None, Some(run)) if run.count > 0 => {
+                self.streak = Some(run);
+                false
+            }
+            _ => false,
+        }
+    }
+    // Returns the bests that beat an earlier one, as "day" or "week"
+    fn update_totals(&mut self, log: &DayLog) -> Vec<(&'static str, Best)>{
+        let days = log.history.iter().map(|e| (e.date, e.value))
+            .chain(std::iter::once((log.today_date, log.today_total)));
+        let mut weeks: BTreeMap<NaiveDate, f64> = BTreeMap::new();
+        let mut best_day: Option<Best> = None;
+        for (date, value) in days {
+            *weeks.entry(date.week(Weekday::Mon).first_day()).or_default() += value;
+            if best_day.is_none_or(|b| value > b.value) {
+                best_day = Some(Best { date, value });
+            }
+        }
+        let best_week = weeks.into_iter()
+            .map(|(date, value)| Best { date, value: units::round(value) })
+            .reduce(|a, b| if b.value > a.value { b } else { a });
+        let mut beaten = Vec::new();
+        for (name, record, best) in [("day", &mut self.day, best_day), ("week", &mut self.week, best_week)] {
+            let Some(best) = best.filter(|b| b.value > 0.0) else { continue };
+            match record {
+                Some(old) if best.value > old.value => {
+                    *old = best;
+                    beaten.push((name, best));
+                }
+                None => *record = Some(best),
+                _ => (),
+            }
+        }
+        beaten
+    }
+}
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct StreakHabit{
     pub name: String,
+    #[serde(flatten)]
+    pub identity: Identity,
+    // Derived from `completions`, kept in the file so it stays readable
     pub streak: u32,
-    pub last_completed: Option<NaiveDate>, 
+    #[serde(default)]
+    pub completions: BTreeSet<NaiveDate>,
+    #[serde(default)]
+    pub schedule: Schedule,
+    // Context for the completions that have any
+    #[serde(default, skip_serializing_if = "BTreeMap::is_empty")]
+    pub notes: BTreeMap<NaiveDate, Annotation>,
+    #[serde(default, skip_serializing_if = "Excused::is_empty")]
+    pub excused: Excused,
+    #[serde(default, skip_serializing_if = "Records::is_empty")]
+    pub records: Records,
+    #[serde(flatten)]
+    pub filing: Filing,
+    // Files written before `completions` existed only have this
+    #[serde(default, rename = "last_completed", skip_serializing)]
+    legacy_last_completed: Option<NaiveDate>,
 }
  
 impl StreakHabit{
     pub fn new(name: String)-> Self{
-        StreakHabit { name, streak: 0, last_completed: None }
+        StreakHabit { name, streak: 0, completions: BTreeSet::new(),
+                      schedule: Schedule::Daily, notes: BTreeMap::new(), excused: Excused::default(),
+                      records: Records::default(), filing: Filing::default(), identity: Identity::default(), legacy_last_completed: None }
     }
     pub fn mark_complete(&mut self, today: NaiveDate){
-        // today = chrono::Local::now().date_naive();
-        match self.last_completed{
-            Some(last_date)=>{
-                if last_date == today {
-                    // Already completed today - do nothing
-                    return;
-                }
-                // Check if yesterday (consecutive)
-                let yesterday = today.pred_opt().unwrap();
-                if last_date == yesterday {
-                    self.streak += 1;
-                } else {
-                    self.streak = 1;
-                }
-                
-                self.last_completed = Some(today);
+        // Already completed today - inserting again does nothing
+        self.completions.insert(today);
+        self.recompute_streak(today);
+    }
+    // Backfill a missed day. Filling a gap joins the runs on either side of it
+    pub fn complete_on(&mut self, date: NaiveDate, today: NaiveDate) -> Result<(), String>{
+        if date > today {
+            return Err(format!("Cannot complete {} ahead of time", date));
+        }
+        if self.completions.contains(&date) {
+            return Err(format!("{} was already completed on {}", self.name, date));
+        }
+        self.completions.insert(date);
+        self.recompute_streak(today);
+        Ok(())
+    }
+    pub fn annotate(&mut self, date: NaiveDate, annotation: Annotation){
+        if !annotation.is_empty() {
+            self.notes.insert(date, annotation);
+        }
+    }
+    pub fn last_completed(&self) -> Option<NaiveDate>{
+        self.completions.last().copied()
+    }
+    pub fn set_schedule(&mut self, schedule: Schedule, today: NaiveDate){
+        self.schedule = schedule;
+        self.recompute_streak(today);
+    }
+    pub fn runs(&self, today: NaiveDate) -> Vec<Run>{
+        self.schedule.runs(&self.completions, &self.excused.days(), today)
+    }
+    pub fn streak_status(&self, today: NaiveDate) -> (u32, StreakStatus){
+        self.schedule.streak(&self.completions, &self.excused.days(), today)
+    }
+    // Size of the run the latest completion belongs to, 0 once it is broken
+    pub fn recompute_streak(&mut self, today: NaiveDate){
+        self.streak = self.streak_status(today).0;
+    }
+    pub fn longest_streak(&self, today: NaiveDate) -> u32{
+        self.runs(today).iter().map(|r| r.count).max().unwrap_or(0)
+    }
+    // Old files only know the last date and the streak length, which is
+    // enough to rebuild the run of days that led up to it
+    fn migrate(&mut self, today: NaiveDate){
+        if let Some(last) = self.legacy_last_completed.take()
+            && self.completions.is_empty()
+        {
+            let days = self.streak.max(1);
+            self.completions.extend(last.iter_days().rev().take(days as usize));
+        }
+        self.recompute_streak(today);
+    }
+}
+
+// What a good day looks like for a quantity habit
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+pub enum Target{
+    AtLeast(f64),
+    AtMost(f64),
+    Range(f64, f64),
+}
+
+impl Target{
+    // Accepts >=2000, <=3, 1500-2500 or a bare number meaning at least
+    pub fn parse(spec: &str) -> Result<Target, String>{
+        let number = |s: &str| s.trim().parse::<f64>().ok().filter(|n| n.is_finite() && *n >= 0.0)
+            .ok_or(())
+            .map_err(|_| format!("'{}' is not a target. Try >=2000, <=3 or 1500-2500", spec));
+        if let Some(n) = spec.strip_prefix(">=") {
+            Ok(Target::AtLeast(number(n)?))
+        } else if let Some(n) = spec.strip_prefix("<=") {
+            Ok(Target::AtMost(number(n)?))
+        } else if let Some((low, high)) = spec.split_once('-') {
+            let (low, high) = (number(low)?, number(high)?);
+            if low > high {
+                return Err(format!("Range {} is upside down, try {}-{}", spec, high, low));
             }
-            None => {
-                self.streak+=1;
-                self.last_completed = Some(today);
-            },
+            Ok(Target::Range(low, high))
+        } else {
+            Ok(Target::AtLeast(number(spec)?))
+        }
+    }
+    // The same target in a unit `factor` times smaller
+    pub fn scale(&self, factor: f64) -> Target{
+        let scale = |value: f64| units::round(value * factor);
+        match *self {
+            Target::AtLeast(min) => Target::AtLeast(scale(min)),
+            Target::AtMost(max) => Target::AtMost(scale(max)),
+            Target::Range(low, high) => Target::Range(scale(low), scale(high)),
+        }
+    }
+    pub fn is_met(&self, value: f64) -> bool{
+        match *self {
+            Target::AtLeast(min) => value >= min,
+            Target::AtMost(max) => value <= max,
+            Target::Range(low, high) => (low..=high).contains(&value),
+        }
+    }
+}
+
+impl Target{
+    // Like Display, with each amount written by `show`
+    pub fn describe(&self, show: impl Fn(f64) -> String) -> String{
+        match *self {
+            Target::AtLeast(min) => format!(">={}", show(min)),
+            Target::AtMost(max) => format!("<={}", show(max)),
+            Target::Range(low, high) => format!("{}-{}", show(low), show(high)),
+        }
+    }
+}
+
+impl fmt::Display for Target{
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result{
+        match self {
+            Target::AtLeast(min) => write!(f, ">={}", min),
+            Target::AtMost(max) => write!(f, "<={}", max),
+            Target::Range(low, high) => write!(f, "{}-{}", low, high),
         }
     }
 }
@@ -43,129 +370,2094 @@ impl StreakHabit{
 #[derive(Debug,Clone,Serialize,Deserialize)]
 pub struct DailyEntry{
     pub date: NaiveDate,
-    pub value: u32,
+    pub value: f64,
+    // Whether the day hit the habit's target, None when it has no target
+    #[serde(default)]
+    pub met: Option<bool>,
+    // Carried over from the day's annotated log entries
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub notes: Vec<Annotation>,
+}
+
+// One amount logged today, at the moment it was logged
+#[derive(Debug,Clone,Serialize,Deserialize)]
+pub struct LogEntry{
+    #[serde(deserialize_with = "timestamp::deserialize")]
+    pub at: Timestamp,
+    pub value: f64,
+    #[serde(flatten)]
+    pub annotation: Annotation,
 }
 
+// Amounts logged through the day, rolled into one entry per day. Shared by
+// every habit that counts something rather than ticking it off
 #[derive(Debug,Clone,Serialize,Deserialize)]
-pub struct HourlyEntry{
-    pub hour: u8,
-    pub value: u32,
+#[serde(from = "StoredDayLog")]
+pub struct DayLog{
+    // Always the sum of today_history. Written out to read the file by,
+    // but worked out again on load
+    pub today_total: f64,
+    pub today_date: NaiveDate,
+    // Oldest first
+    pub today_history: Vec<LogEntry>,
+    pub history: Vec<DailyEntry>,
+}
+
+// Entries in older files only kept the hour they were logged in, and
+// some of those had no time at all
+#[derive(Deserialize)]
+struct StoredEntry{
+    hour: Option<u8>,
+    value: f64,
+    #[serde(default, deserialize_with = "timestamp::deserialize_option")]
+    at: Option<Timestamp>,
+    #[serde(flatten)]
+    annotation: Annotation,
+}
+
+// An older reset zeroed today_total but kept the entries, so the stored
+// total isn't read at all
+#[derive(Deserialize)]
+struct StoredDayLog{
+    today_date: NaiveDate,
+    today_history: Vec<StoredEntry>,
+    history: Vec<DailyEntry>,
+}
+
+impl From<StoredDayLog> for DayLog{
+    fn from(stored: StoredDayLog) -> Self{
+        let today = stored.today_date;
+        // An hour-only entry is placed at the start of its hour. Hours before
+        // the day starts were logged after midnight, on the next date
+        let home = timestamp::reading_home();
+        let start_of = |hour: u8| {
+            let at = home.at_home_time(today.and_time(NaiveTime::MIN) + TimeDelta::hours(hour.min(23) as i64));
+            if home.day_of(at) < today { at + TimeDelta::days(1) } else { at }
+        };
+        let mut today_history: Vec<LogEntry> = stored.today_history.into_iter()
+            .map(|e| LogEntry { at: e.at.unwrap_or_else(|| start_of(e.hour.unwrap_or(0))), value: e.value, annotation: e.annotation })
+            .collect();
+        today_history.sort_by_key(|e| e.at);
+        let mut log = DayLog { today_total: 0.0, today_date: today, today_history, history: stored.history };
+        log.retotal();
+        log
+    }
+}
+
+impl DayLog{
+    pub fn new(today: NaiveDate) -> Self{
+        DayLog { today_total: 0.0, today_date: today, today_history: Vec::new(), history: Vec::new() }
+    }
+    // `judge` decides whether a finished day counts as met
+    pub fn record(&mut self, amount: f64, annotation: Annotation, clock: &dyn Clock, judge: impl Fn(f64) -> Option<bool>){
+        // A log just after midnight can still belong to the day before
+        let now = clock.now();
+        self.roll_over(clock.today(), judge);
+        // Kept in time order, a log can't be assumed to be the latest
+        let index = self.today_history.partition_point(|e| e.at <= now);
+        self.today_history.insert(index, LogEntry { at: now, value: amount, annotation });
+        self.retotal();
+    }
+    // today_total only ever comes from the entries, so a fixed entry fixes the day
+    fn retotal(&mut self){
+        self.today_total = units::round(self.today_history.iter().map(|e| e.value).sum());
+    }
+    // Entry `number` of today, counting from 1 as `entries` lists them
+    fn entry_index(&self, number: usize) -> Result<usize, String>{
+        match self.today_history.len() {
+            0 => Err("Nothing logged today".to_string()),
+            n if (1..=n).contains(&number) => Ok(number - 1),
+            n => Err(format!("No entry {}, today has entries 1-{}", number, n)),
+        }
+    }
+    pub fn remove_entry(&mut self, number: usize) -> Result<LogEntry, String>{
+        let index = self.entry_index(number)?;
+        let total = self.today_total - self.today_history[index].value;
+        if units::round(total) < 0.0 {
+            return Err("That would take today's total below 0".to_string());
+        }
+        let entry = self.today_history.remove(index);
+        self.retotal();
+        Ok(entry)
+    }
+    // Returns the amount the entry had before
+    pub fn amend_entry(&mut self, number: usize, value: f64) -> Result<f64, String>{
+        let index = self.entry_index(number)?;
+        let old = self.today_history[index].value;
+        if units::round(self.today_total - old + value) < 0.0 {
+            return Err("That would take today's total below 0".to_string());
+        }
+        self.today_history[index].value = value;
+        self.retotal();
+        Ok(old)
+    }
+    // Today's amounts added up per hour at home, for stats
+    pub fn by_hour(&self, home: Home) -> BTreeMap<u32, f64>{
+        let mut hours: BTreeMap<u32, f64> = BTreeMap::new();
+        for entry in &self.today_history {
+            let total = hours.entry(home.home_time(entry.at).hour()).or_default();
+            *total = units::round(*total + entry.value);
+        }
+        hours
+    }
+    // Every amount, past and today, multiplied by `factor` for a new unit
+    pub fn scale(&mut self, factor: f64){
+        for entry in &mut self.history {
+            entry.value = units::round(entry.value * factor);
+        }
+        for entry in &mut self.today_history {
+            entry.value = units::round(entry.value * factor);
+        }
+        self.retotal();
+    }
+    pub fn clear_today(&mut self){
+        self.today_history.clear();
+        self.retotal();
+    }
+    // Archives the tracked day once `today` has moved past it, with a zero
+    // entry for every day in between that nothing was logged on
+    pub fn roll_over(&mut self, today: NaiveDate, judge: impl Fn(f64) -> Option<bool>){
+        if today <= self.today_date {
+            return;
+        }
+        let notes = self.today_history.iter()
+            .filter(|e| !e.annotation.is_empty())
+            .map(|e| e.annotation.clone())
+            .collect();
+        let entry = DailyEntry{date: self.today_date, value: self.today_total, met: judge(self.today_total), notes};
+        self.history.push(entry);
+        for date in self.today_date.iter_days().skip(1).take_while(|d| *d < today) {
+            self.history.push(DailyEntry { date, value: 0.0, met: judge(0.0), notes: Vec::new() });
+        }
+        self.today_date = today;
+        self.today_total = 0.0;
+        self.today_history.clear();
+    }
+    // Older files skipped the days nothing was logged, this adds them back
+    pub fn fill_gaps(&mut self, judge: impl Fn(f64) -> Option<bool>){
+        self.history.sort_by_key(|e| e.date);
+        let logged: BTreeSet<NaiveDate> = self.history.iter().map(|e| e.date).collect();
+        let Some(&first) = logged.first() else { return };
+        let missing: Vec<DailyEntry> = first.iter_days()
+            .take_while(|d| *d < self.today_date)
+            .filter(|d| !logged.contains(d))
+            .map(|date| DailyEntry { date, value: 0.0, met: judge(0.0), notes: Vec::new() })
+            .collect();
+        if !missing.is_empty() {
+            self.history.extend(missing);
+            self.history.sort_by_key(|e| e.date);
+        }
+    }
+    pub fn rejudge(&mut self, judge: impl Fn(f64) -> Option<bool>){
+        for entry in &mut self.history {
+            entry.met = judge(entry.value);
+        }
+    }
+    // Every annotation with the day it belongs to, oldest first
+    pub fn annotations(&self) -> Vec<(NaiveDate, Annotation)>{
+        let archived = self.history.iter()
+            .flat_map(|e| e.notes.iter().map(|n| (e.date, n.clone())));
+        let today = self.today_history.iter()
+            .filter(|e| !e.annotation.is_empty())
+            .map(|e| (self.today_date, e.annotation.clone()));
+        archived.chain(today).collect()
+    }
+    // First day anything was tracked
+    pub fn first_date(&self) -> NaiveDate{
+        self.history.first().map_or(self.today_date, |e| e.date.min(self.today_date))
+    }
 }
+
 #[derive(Debug,Clone,Serialize,Deserialize)]
 pub struct QuantityHabit{
     pub name: String,
+    #[serde(flatten)]
+    pub identity: Identity,
+    // What amounts are stored in
     pub unit: String,
-    pub unit_size: u32,
-    pub today_total: u32,
-    pub today_date: NaiveDate,
-    pub today_history: Vec<HourlyEntry>,
-    pub history: Vec<DailyEntry>,
+    pub unit_size: f64,
+    // Shown instead of `unit` when set, e.g. l for a habit kept in ml
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub display_unit: Option<String>,
+    #[serde(flatten)]
+    pub log: DayLog,
+    #[serde(default)]
+    pub target: Option<Target>,
+    // Days in a row the target was met, 0 without a target
+    #[serde(default)]
+    pub streak: u32,
+    #[serde(default, skip_serializing_if = "Excused::is_empty")]
+    pub excused: Excused,
+    #[serde(default, skip_serializing_if = "Records::is_empty")]
+    pub records: Records,
+    #[serde(flatten)]
+    pub filing: Filing,
 }
 
 impl QuantityHabit{
-    pub fn new(name: String, unit: String, unit_size: u32) -> Self{
-        // let today_date = chrono::Local::now().date_naive();
-        // let today_history: Vec<HourlyEntry> = Vec::new();
-        // let history: Vec<DailyEntry> = Vec::new(); 
-        QuantityHabit { name, unit, unit_size, today_total: 0, 
-                        today_date: chrono::Local::now().date_naive(), 
-                        today_history: Vec::new(),
-                        history: Vec::new()}
-    }
-    pub fn log(&mut self, amount: u32){
-        let now = chrono::Local::now();
-        let today = now.date_naive();
-        let hour = now.hour() as u8;
-        // check if today's date as last saved entry
-        // if it is not the same push the last date's history
-        // and reset current tracking hour vector to be empty
-        if today!=self.today_date{
-            // push end of date value
-            let entry = DailyEntry{date: self.today_date, value: self.today_total};
-            self.history.push(entry);
-            // change habit date to current date and set today_total to '0' and reset today_history
-            self.today_date = today;
-            self.today_total = 0;
-            self.today_history.clear();
-        }
-        // if today's date is same as habit's date
-        else{
-            let log_amount = amount*self.unit_size;
-            self.today_total+=log_amount;
-            self.today_history.push(HourlyEntry { hour, value: log_amount });
+    pub fn new(name: String, unit: String, unit_size: f64, today: NaiveDate) -> Self{
+        QuantityHabit { name, unit, unit_size, display_unit: None,
+                        log: DayLog::new(today),
+                        target: None,
+                        streak: 0,
+                        excused: Excused::default(),
+                        records: Records::default(), filing: Filing::default(), identity: Identity::default()}
+    }
+    pub fn set_target(&mut self, target: Option<Target>){
+        self.target = target;
+        // Re-judge past days against the new goal
+        self.log.rejudge(|value| target.map(|t| t.is_met(value)));
+        self.recompute_streak();
+    }
+    // An amount in `unit`, written in the display unit
+    pub fn show(&self, value: f64) -> String{
+        units::show(value, &self.unit, self.display_unit.as_deref())
+    }
+    pub fn today_met(&self) -> Option<bool>{
+        self.target.map(|t| t.is_met(self.log.today_total))
+    }
+    pub fn met_days(&self) -> BTreeSet<NaiveDate>{
+        let mut met_days: BTreeSet<NaiveDate> = self.log.history.iter()
+            .filter(|e| e.met == Some(true))
+            .map(|e| e.date)
+            .collect();
+        if self.today_met() == Some(true) {
+            met_days.insert(self.log.today_date);
         }
+        met_days
+    }
+    // Today only joins the streak once it meets the target, an unfinished
+    // day doesn't break it
+    pub fn streak_status(&self, today: NaiveDate) -> (u32, StreakStatus){
+        Schedule::Daily.streak(&self.met_days(), &self.excused.days(), today)
+    }
+    pub fn recompute_streak(&mut self){
+        self.streak = self.streak_status(self.log.today_date).0;
+    }
+    pub fn log(&mut self, amount: f64, annotation: Annotation, clock: &dyn Clock){
+        let target = self.target;
+        self.log.record(amount, annotation, clock, |value| target.map(|t| t.is_met(value)));
+        self.recompute_streak();
     }
 }
 
-#[derive(Debug, Clone, Serialize, Deserialize)]
-pub enum Habit{
-    Streak(StreakHabit),
-    Quantity(QuantityHabit),
+// Something to do less of. A day is clean while its total stays within
+// `max`, and days nothing was logged are clean too
+#[derive(Debug,Clone,Serialize,Deserialize)]
+pub struct LimitHabit{
+    pub name: String,
+    #[serde(flatten)]
+    pub identity: Identity,
+    pub unit: String,
+    pub unit_size: f64,
+    pub max: f64,
+    #[serde(flatten)]
+    pub log: DayLog,
+    // Clean days in a row up to today
+    pub streak: u32,
+    #[serde(default, skip_serializing_if = "Excused::is_empty")]
+    pub excused: Excused,
+    #[serde(default, skip_serializing_if = "Records::is_empty")]
+    pub records: Records,
+    #[serde(flatten)]
+    pub filing: Filing,
 }
 
-
-impl Habit{
-    pub fn new_streak(name: String) -> Self{
-        Habit::Streak(StreakHabit::new(name))
+impl LimitHabit{
+    pub fn new(name: String, unit: String, unit_size: f64, max: f64, today: NaiveDate) -> Self{
+        LimitHabit { name, unit, unit_size, max,
+                     log: DayLog::new(today),
+                     streak: 0, excused: Excused::default(), records: Records::default(), filing: Filing::default(), identity: Identity::default() }
     }
-    pub fn new_quantity(name: String, unit: String, unit_size: u32)->Self{
-        Habit::Quantity(QuantityHabit::new(name, unit, unit_size))
+    pub fn log(&mut self, amount: f64, annotation: Annotation, clock: &dyn Clock){
+        let max = self.max;
+        self.log.record(amount, annotation, clock, |value| Some(value <= max));
+        self.recompute_streak(clock.today());
     }
-    pub fn complete(&mut self) -> Result<String, String>{
-        let today = chrono::Local::now().date_naive();
-        match self{
-            Habit::Streak(streak_habit) => {
-                streak_habit.mark_complete(today);
-                Ok(format!("Streak: {} days", streak_habit.streak))
-            },
-            Habit::Quantity(_) => {
-                Err("Cannot complete quantity habit. Use 'log <amount>' instead".to_string())            },
+    pub fn show(&self, value: f64) -> String{
+        format!("{}{}", units::number(value), self.unit)
+    }
+    pub fn over_today(&self) -> bool{
+        self.log.today_total > self.max
+    }
+    // Days that went over and weren't excused
+    pub fn over_days(&self) -> BTreeSet<NaiveDate>{
+        let mut days: BTreeSet<NaiveDate> = self.log.history.iter()
+            .filter(|e| e.value > self.max)
+            .map(|e| e.date)
+            .collect();
+        if self.over_today() {
+            days.insert(self.log.today_date);
         }
+        days.retain(|d| self.excused.get(*d).is_none());
+        days
     }
-    // complete for quantity
-    pub fn log_amount(&mut self, amount: u32) -> Result<String, String> {
-        match self {
-            Habit::Quantity(q) => {
-                q.log(amount);
-                Ok(format!("Logged {}{}", q.today_total, q.unit))
-            }
-            Habit::Streak(_) => {
-                Err("Cannot log quantity for streak habit. Use 'complete' instead".to_string())
+    // Clean stretches between the days that went over, oldest first.
+    // Excused days don't add to a run's length
+    pub fn runs(&self, today: NaiveDate) -> Vec<Run>{
+        let excused = self.excused.days();
+        let mut runs = Vec::new();
+        let mut start = self.log.first_date();
+        for over in self.over_days().into_iter().chain(today.succ_opt()) {
+            if over > start {
+                let end = over.pred_opt().unwrap_or(over).min(today);
+                let count = (end - start).num_days() as u32 + 1 - excused.range(start..=end).count() as u32;
+                runs.push(Run { start, end, count });
             }
+            start = over.succ_opt().unwrap_or(over);
         }
+        runs
+    }
+    pub fn clean_days(&self, today: NaiveDate) -> u32{
+        self.runs(today).last()
+            .filter(|r| r.end == today)
+            .map_or(0, |r| r.count)
+    }
+    pub fn recompute_streak(&mut self, today: NaiveDate){
+        self.streak = self.clean_days(today);
+    }
+    pub fn longest_streak(&self, today: NaiveDate) -> u32{
+        self.runs(today).iter().map(|r| r.count).max().unwrap_or(0)
     }
 }
 
-// Shared behaviours
-pub trait HabitFunctions {
-    fn name(&self)->&str;
-    fn reset(&mut self);
-    fn display_line(&self) -> String;
+#[derive(Debug,Clone,Serialize,Deserialize)]
+pub struct ChecklistItem{
+    pub name: String,
+    pub completions: BTreeSet<NaiveDate>,
 }
 
-impl HabitFunctions for Habit{
-    fn name(&self)->&str {
-        match self{
-            Habit::Streak(h) => &h.name,
-            Habit::Quantity(q) => &q.name,
+// How many items make the whole checklist count as done for the day
+#[derive(Debug, Clone, Copy, Default, PartialEq, Serialize, Deserialize)]
+pub enum CompletionRule{
+    #[default]
+    All,
+    AtLeast(u32),
+}
+
+impl CompletionRule{
+    pub fn parse(spec: &str) -> Result<CompletionRule, String>{
+        match spec {
+            "all" => Ok(CompletionRule::All),
+            "any" => Ok(CompletionRule::AtLeast(1)),
+            n => match n.parse::<u32>() {
+                Ok(n) if n >= 1 => Ok(CompletionRule::AtLeast(n)),
+                _ => Err(format!("'{}' is not a rule. Try all, any or a number of items", spec)),
+            },
         }
     }
+}
 
-    fn reset(&mut self) {
-        match self {
-            Habit::Streak(streak_habit) => streak_habit.streak=0,
-            Habit::Quantity(quantity_habit) => quantity_habit.today_total=0,
+// One habit made of several steps, e.g. a morning routine
+#[derive(Debug,Clone,Serialize,Deserialize)]
+pub struct ChecklistHabit{
+    pub name: String,
+    #[serde(flatten)]
+    pub identity: Identity,
+    pub items: Vec<ChecklistItem>,
+    #[serde(default)]
+    pub rule: CompletionRule,
+    #[serde(default)]
+    pub schedule: Schedule,
+    pub streak: u32,
+    #[serde(default, skip_serializing_if = "BTreeMap::is_empty")]
+    pub notes: BTreeMap<NaiveDate, Annotation>,
+    #[serde(default, skip_serializing_if = "Excused::is_empty")]
+    pub excused: Excused,
+    #[serde(default, skip_serializing_if = "Records::is_empty")]
+    pub records: Records,
+    #[serde(flatten)]
+    pub filing: Filing,
+}
+
+impl ChecklistHabit{
+    pub fn new(name: String, items: Vec<String>) -> Self{
+        let items = items.into_iter()
+            .map(|name| ChecklistItem { name, completions: BTreeSet::new() })
+            .collect();
+        ChecklistHabit { name, items, rule: CompletionRule::All, schedule: Schedule::Daily, streak: 0,
+                         notes: BTreeMap::new(), excused: Excused::default(), records: Records::default(), filing: Filing::default(), identity: Identity::default() }
+    }
+    pub fn check(&mut self, item: &str, date: NaiveDate, today: NaiveDate) -> Result<(), String>{
+        if date > today {
+            return Err(format!("Cannot check off {} ahead of time", date));
         }
+        self.item_mut(item)?.completions.insert(date);
+        self.recompute_streak(today);
+        Ok(())
     }
-    
-    fn display_line(&self) -> String {
-        match self{
-            Habit::Streak(s) => {
-                format!("[S] {}: streak {}",s.name,s.streak)
-            },
-            Habit::Quantity(q) => {
-                format!("[Q] {}: {}{} today",q.name, q.today_total, q.unit )
-            },
+    pub fn uncheck(&mut self, item: &str, date: NaiveDate, today: NaiveDate) -> Result<(), String>{
+        self.item_mut(item)?.completions.remove(&date);
+        self.recompute_streak(today);
+        Ok(())
+    }
+    pub fn annotate(&mut self, date: NaiveDate, annotation: Annotation){
+        if !annotation.is_empty() {
+            self.notes.insert(date, annotation);
+        }
+    }
+    // Ticks every item, for `complete` on the whole checklist
+    pub fn check_all(&mut self, date: NaiveDate, today: NaiveDate){
+        for item in &mut self.items {
+            item.completions.insert(date);
+        }
+        self.recompute_streak(today);
+    }
+    pub fn set_rule(&mut self, rule: CompletionRule, today: NaiveDate){
+        self.rule = rule;
+        self.recompute_streak(today);
+    }
+    pub fn checked_on(&self, date: NaiveDate) -> usize{
+        self.items.iter().filter(|i| i.completions.contains(&date)).count()
+    }
+    pub fn required(&self) -> usize{
+        match self.rule {
+            CompletionRule::All => self.items.len(),
+            CompletionRule::AtLeast(n) => (n as usize).min(self.items.len()),
+        }
+    }
+    pub fn is_complete_on(&self, date: NaiveDate) -> bool{
+        !self.items.is_empty() && self.checked_on(date) >= self.required()
+    }
+    // Days the checklist as a whole counted as done
+    pub fn completed_days(&self) -> BTreeSet<NaiveDate>{
+        self.items.iter()
+            .flat_map(|i| i.completions.iter().copied())
+            .filter(|d| self.is_complete_on(*d))
+            .collect()
+    }
+    pub fn streak_status(&self, today: NaiveDate) -> (u32, StreakStatus){
+        self.schedule.streak(&self.completed_days(), &self.excused.days(), today)
+    }
+    pub fn recompute_streak(&mut self, today: NaiveDate){
+        self.streak = self.streak_status(today).0;
+    }
+    fn item_mut(&mut self, item: &str) -> Result<&mut ChecklistItem, String>{
+        let names = self.items.iter().map(|i| i.name.as_str()).collect::<Vec<_>>().join(", ");
+        self.items.iter_mut()
+            .find(|i| i.name == item)
+            .ok_or(format!("{} has no item '{}'. Items: {}", self.name, item, names))
+    }
+}
+
+#[derive(Debug,Clone,Serialize,Deserialize)]
+pub struct Session{
+    #[serde(deserialize_with = "timestamp::deserialize")]
+    pub start: Timestamp,
+    #[serde(deserialize_with = "timestamp::deserialize")]
+    pub end: Timestamp,
+    #[serde(flatten)]
+    pub annotation: Annotation,
+}
+
+impl Session{
+    pub fn minutes(&self) -> u32{
+        ((self.end - self.start).num_seconds().max(0) as u32 + 30) / 60
+    }
+}
+
+// Time spent on something, measured with start/stop and kept as minutes per day
+#[derive(Debug,Clone,Serialize,Deserialize)]
+pub struct TimedHabit{
+    pub name: String,
+    #[serde(flatten)]
+    pub identity: Identity,
+    // When the timer was started, kept in the file so it survives a restart
+    #[serde(default, deserialize_with = "timestamp::deserialize_option")]
+    pub running: Option<Timestamp>,
+    #[serde(default)]
+    pub sessions: Vec<Session>,
+    #[serde(flatten)]
+    pub log: DayLog,
+    #[serde(default, skip_serializing_if = "Records::is_empty")]
+    pub records: Records,
+    #[serde(flatten)]
+    pub filing: Filing,
+}
+
+impl TimedHabit{
+    pub fn new(name: String, today: NaiveDate) -> Self{
+        TimedHabit { name, running: None, sessions: Vec::new(),
+                     log: DayLog::new(today), records: Records::default(), filing: Filing::default(), identity: Identity::default() }
+    }
+    pub fn start(&mut self, clock: &dyn Clock) -> Result<(), String>{
+        if let Some(since) = self.running {
+            return Err(format!("{} has been running since {}", self.name, clock.home().home_time(since).format("%H:%M")));
         }
+        self.running = Some(clock.now());
+        Ok(())
     }
-}
\ No newline at end of file
+    pub fn stop(&mut self, clock: &dyn Clock, annotation: Annotation) -> Result<Session, String>{
+        let start = self.running.take()
+            .ok_or(format!("{} isn't running. Use 'start {}' first", self.name, self.name))?;
+        let session = Session { start, end: clock.now(), annotation };
+        // The note lives on the session, not on the minutes it adds up to
+        self.log.record(session.minutes() as f64, Annotation::default(), clock, |_| None);
+        self.sessions.push(session.clone());
+        Ok(session)
+    }
+    pub fn elapsed_minutes(&self, now: Timestamp) -> Option<u32>{
+        self.running.map(|start| Session { start, end: now, annotation: Annotation::default() }.minutes())
+    }
+}
+
+#[derive(Debug,Clone,Serialize,Deserialize)]
+#[serde(from = "StoredRelapse")]
+pub struct Relapse{
+    pub at: Timestamp,
+    // The habit day it ended a run on, which depends on the day start and
+    // timezone when it happened
+    pub day: NaiveDate,
+    #[serde(default)]
+    pub note: Option<String>,
+}
+
+// Older files only have the time
+#[derive(Deserialize)]
+struct StoredRelapse{
+    #[serde(deserialize_with = "timestamp::deserialize")]
+    at: Timestamp,
+    day: Option<NaiveDate>,
+    #[serde(default)]
+    note: Option<String>,
+}
+
+impl From<StoredRelapse> for Relapse{
+    fn from(stored: StoredRelapse) -> Self{
+        let day = stored.day.unwrap_or_else(|| timestamp::reading_home().day_of(stored.at));
+        Relapse { at: stored.at, day, note: stored.note }
+    }
+}
+
+// Something given up entirely, measured in days since the last relapse
+#[derive(Debug,Clone,Serialize,Deserialize)]
+pub struct AbstinenceHabit{
+    pub name: String,
+    #[serde(flatten)]
+    pub identity: Identity,
+    pub started: NaiveDate,
+    pub relapses: Vec<Relapse>,
+    // Days clean as of the last time it was worked out
+    pub streak: u32,
+    #[serde(default, skip_serializing_if = "Records::is_empty")]
+    pub records: Records,
+    #[serde(flatten)]
+    pub filing: Filing,
+}
+
+impl AbstinenceHabit{
+    pub fn new(name: String, started: NaiveDate, today: NaiveDate) -> Self{
+        let mut habit = AbstinenceHabit { name, started, relapses: Vec::new(), streak: 0, records: Records::default(), filing: Filing::default(), identity: Identity::default() };
+        habit.recompute_streak(today);
+        habit
+    }
+    pub fn relapse(&mut self, clock: &dyn Clock, note: Option<String>){
+        self.relapses.push(Relapse { at: clock.now(), day: clock.today(), note });
+        self.relapses.sort_by_key(|r| r.at);
+        self.recompute_streak(clock.today());
+    }
+    // Start of the current clean run
+    pub fn clean_since(&self) -> NaiveDate{
+        self.relapses.last().map_or(self.started, |r| r.day)
+    }
+    pub fn days_clean(&self, today: NaiveDate) -> u32{
+        (today - self.clean_since()).num_days().max(0) as u32
+    }
+    pub fn recompute_streak(&mut self, today: NaiveDate){
+        self.streak = self.days_clean(today);
+    }
+    // Every clean run, each ending on the day of the relapse that ended
+    // it, the current one last
+    pub fn runs(&self, today: NaiveDate) -> Vec<Run>{
+        let mut runs = Vec::new();
+        let mut since = self.started;
+        let relapse_days = self.relapses.iter().map(|r| r.day);
+        for end in relapse_days.chain(std::iter::once(today)) {
+            runs.push(Run { start: since, end, count: (end - since).num_days().max(0) as u32 });
+            since = end;
+        }
+        runs
+    }
+    // Lengths in days of every clean run, the current one last
+    pub fn clean_runs(&self, today: NaiveDate) -> Vec<u32>{
+        self.runs(today).iter().map(|r| r.count).collect()
+    }
+    pub fn longest_clean(&self, today: NaiveDate) -> u32{
+        self.clean_runs(today).into_iter().max().unwrap_or(0)
+    }
+    // Average relapses per 30 days since starting
+    pub fn relapses_per_month(&self, today: NaiveDate) -> f64{
+        let days = (today - self.started).num_days().max(1) as f64;
+        self.relapses.len() as f64 * 30.0 / days
+    }
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub enum Habit{
+    Streak(StreakHabit),
+    Quantity(QuantityHabit),
+    Limit(LimitHabit),
+    Abstinence(AbstinenceHabit),
+    Timed(TimedHabit),
+    Checklist(ChecklistHabit),
+}
+
+
+impl Habit{
+    pub fn new_streak(name: String, clock: &dyn Clock) -> Self{
+        Habit::Streak(StreakHabit::new(name)).created(clock)
+    }
+    pub fn new_quantity(name: String, unit: String, unit_size: f64, clock: &dyn Clock)->Self{
+        Habit::Quantity(QuantityHabit::new(name, unit, unit_size, clock.today())).created(clock)
+    }
+    pub fn new_limit(name: String, unit: String, unit_size: f64, max: f64, clock: &dyn Clock)->Self{
+        Habit::Limit(LimitHabit::new(name, unit, unit_size, max, clock.today())).created(clock)
+    }
+    pub fn new_checklist(name: String, items: Vec<String>, clock: &dyn Clock)->Self{
+        Habit::Checklist(ChecklistHabit::new(name, items)).created(clock)
+    }
+    pub fn new_timed(name: String, clock: &dyn Clock)->Self{
+        Habit::Timed(TimedHabit::new(name, clock.today())).created(clock)
+    }
+    pub fn new_abstinence(name: String, started: NaiveDate, clock: &dyn Clock)->Self{
+        Habit::Abstinence(AbstinenceHabit::new(name, started, clock.today())).created(clock)
+    }
+    fn created(mut self, clock: &dyn Clock) -> Self{
+        *self.identity_mut() = Identity::new(clock.now());
+        self
+    }
+    pub fn identity(&self) -> &Identity{
+        match self {
+            Habit::Streak(s) => &s.identity,
+            Habit::Quantity(q) => &q.identity,
+            Habit::Limit(l) => &l.identity,
+            Habit::Abstinence(a) => &a.identity,
+            Habit::Timed(t) => &t.identity,
+            Habit::Checklist(c) => &c.identity,
+        }
+    }
+    fn identity_mut(&mut self) -> &mut Identity{
+        match self {
+            Habit::Streak(s) => &mut s.identity,
+            Habit::Quantity(q) => &mut q.identity,
+            Habit::Limit(l) => &mut l.identity,
+            Habit::Abstinence(a) => &mut a.identity,
+            Habit::Timed(t) => &mut t.identity,
+            Habit::Checklist(c) => &mut c.identity,
+        }
+    }
+    pub fn id(&self) -> &str{
+        &self.identity().id
+    }
+    // First day anything was recorded, the best guess at when an old
+    // habit was created
+    fn first_day(&self) -> Option<NaiveDate>{
+        match self {
+            Habit::Streak(s) => s.completions.first().copied(),
+            Habit::Quantity(q) => Some(q.log.first_date()),
+            Habit::Limit(l) => Some(l.log.first_date()),
+            Habit::Timed(t) => Some(t.log.first_date()),
+            Habit::Abstinence(a) => Some(a.started),
+            Habit::Checklist(c) => c.items.iter().filter_map(|i| i.completions.first()).min().copied(),
+        }
+    }
+    // Brings habits loaded from older habits.json files up to date
+    pub fn migrate(&mut self, today: NaiveDate){
+        match self {
+            Habit::Streak(s) => s.migrate(today),
+            // set_target judges the filled in days
+            Habit::Quantity(q) => {
+                q.log.fill_gaps(|_| None);
+                q.set_target(q.target);
+            }
+            Habit::Limit(l) => {
+                let max = l.max;
+                l.log.fill_gaps(|value| Some(value <= max));
+            }
+            Habit::Timed(t) => t.log.fill_gaps(|_| None),
+            _ => (),
+        }
+        self.roll_over(today);
+        self.refresh_streak(today);
+        self.update_records(today);
+    }
+    // Moves logged amounts on to `today`, so a day that has ended is never
+    // shown as today's total
+    pub fn roll_over(&mut self, today: NaiveDate){
+        match self {
+            Habit::Quantity(q) => {
+                let target = q.target;
+                q.log.roll_over(today, |value| target.map(|t| t.is_met(value)));
+            }
+            Habit::Limit(l) => {
+                let max = l.max;
+                l.log.roll_over(today, |value| Some(value <= max));
+            }
+            Habit::Timed(t) => t.log.roll_over(today, |_| None),
+            Habit::Streak(_) | Habit::Abstinence(_) | Habit::Checklist(_) => (),
+        }
+    }
+    pub fn refresh_streak(&mut self, today: NaiveDate){
+        match self {
+            Habit::Streak(s) => s.recompute_streak(today),
+            Habit::Quantity(q) => q.recompute_streak(),
+            Habit::Limit(l) => l.recompute_streak(today),
+            Habit::Abstinence(a) => a.recompute_streak(today),
+            Habit::Timed(_) => (),
+            Habit::Checklist(c) => c.recompute_streak(today),
+        }
+    }
+    // The streak worked out afresh for `today`, so a stored value that
+    // went stale overnight never shows. None for habits without one
+    pub fn streak_status(&self, today: NaiveDate) -> Option<(u32, StreakStatus)>{
+        match self {
+            Habit::Streak(s) => Some(s.streak_status(today)),
+            Habit::Quantity(q) => q.target.map(|_| q.streak_status(today)),
+            Habit::Checklist(c) => Some(c.streak_status(today)),
+            // Clean days can only grow, going over shows on its own
+            Habit::Limit(l) => Some((l.clean_days(today), StreakStatus::Current)),
+            Habit::Abstinence(a) => Some((a.days_clean(today), StreakStatus::Current)),
+            Habit::Timed(_) => None,
+        }
+    }
+    pub fn records(&self) -> &Records{
+        match self {
+            Habit::Streak(s) => &s.records,
+            Habit::Quantity(q) => &q.records,
+            Habit::Limit(l) => &l.records,
+            Habit::Abstinence(a) => &a.records,
+            Habit::Timed(t) => &t.records,
+            Habit::Checklist(c) => &c.records,
+        }
+    }
+    fn records_mut(&mut self) -> &mut Records{
+        match self {
+            Habit::Streak(s) => &mut s.records,
+            Habit::Quantity(q) => &mut q.records,
+            Habit::Limit(l) => &mut l.records,
+            Habit::Abstinence(a) => &mut a.records,
+            Habit::Timed(t) => &mut t.records,
+            Habit::Checklist(c) => &mut c.records,
+        }
+    }
+    pub fn filing(&self) -> &Filing{
+        match self {
+            Habit::Streak(s) => &s.filing,
+            Habit::Quantity(q) => &q.filing,
+            Habit::Limit(l) => &l.filing,
+            Habit::Abstinence(a) => &a.filing,
+            Habit::Timed(t) => &t.filing,
+            Habit::Checklist(c) => &c.filing,
+        }
+    }
+    fn filing_mut(&mut self) -> &mut Filing{
+        match self {
+            Habit::Streak(s) => &mut s.filing,
+            Habit::Quantity(q) => &mut q.filing,
+            Habit::Limit(l) => &mut l.filing,
+            Habit::Abstinence(a) => &mut a.filing,
+            Habit::Timed(t) => &mut t.filing,
+            Habit::Checklist(c) => &mut c.filing,
+        }
+    }
+    pub fn tags(&self) -> &BTreeSet<String>{
+        &self.filing().tags
+    }
+    pub fn archived(&self) -> Option<NaiveDate>{
+        self.filing().archived
+    }
+    pub fn is_archived(&self) -> bool{
+        self.archived().is_some()
+    }
+    pub fn archive(&mut self, clock: &dyn Clock) -> Result<String, String>{
+        if let Some(since) = self.archived() {
+            return Err(format!("{} has been archived since {}", self.name(), since));
+        }
+        // A running timer would keep counting while nobody can see it
+        if let Habit::Timed(t) = self && t.running.is_some() {
+            return Err(format!("{} is running. Use 'stop {}' first", t.name, t.name));
+        }
+        self.filing_mut().archived = Some(clock.today());
+        Ok(format!("Archived {}. Its history is kept, 'restore {}' brings it back", self.name(), self.name()))
+    }
+    pub fn restore(&mut self, clock: &dyn Clock) -> Result<String, String>{
+        if self.filing_mut().archived.take().is_none() {
+            return Err(format!("{} isn't archived", self.name()));
+        }
+        self.roll_over(clock.today());
+        self.refresh_streak(clock.today());
+        Ok(format!("Restored {}", self.name()))
+    }
+    // Everything else, history included, stays with the habit
+    pub fn rename(&mut self, new_name: &str) -> Result<String, String>{
+        if !is_valid_habit_name(new_name) {
+            return Err(format!("'{}' is not a valid name. Use lowercase letters, numbers and dashes", new_name));
+        }
+        let name = match self {
+            Habit::Streak(s) => &mut s.name,
+            Habit::Quantity(q) => &mut q.name,
+            Habit::Limit(l) => &mut l.name,
+            Habit::Abstinence(a) => &mut a.name,
+            Habit::Timed(t) => &mut t.name,
+            Habit::Checklist(c) => &mut c.name,
+        };
+        if name == new_name {
+            return Err(format!("{} is already called that", name));
+        }
+        let old = std::mem::replace(name, new_name.to_string());
+        Ok(format!("Renamed {} to {}", old, new_name))
+    }
+    // Changes the unit, converting every amount so it keeps its meaning, or
+    // the size of one logged step, which past days are already multiplied out of
+    pub fn edit(&mut self, field: &str, value: &str) -> Result<String, String>{
+        if field == "display" {
+            let Habit::Quantity(q) = self else {
+                return Err("Only quantity habits have a display unit".to_string());
+            };
+            if value == "none" {
+                q.display_unit = None;
+                return Ok(format!("{} is shown in {} again", q.name, q.unit));
+            }
+            units::convert(1.0, &q.unit, value)?;
+            q.display_unit = Some(value.to_string());
+            return Ok(format!("{} is now shown in {}", q.name, value));
+        }
+        let (name, unit, unit_size) = match self {
+            Habit::Quantity(q) => (&q.name, &mut q.unit, &mut q.unit_size),
+            Habit::Limit(l) => (&l.name, &mut l.unit, &mut l.unit_size),
+            _ => return Err("Only quantity and limit habits have a unit to edit".to_string()),
+        };
+        match field {
+            "unit" => {
+                let factor = units::convert(1.0, unit, value)
+                    .map_err(|e| format!("{}, past amounts would change meaning", e))?;
+                let old = std::mem::replace(unit, value.to_string());
+                let name = name.clone();
+                self.scale(factor);
+                Ok(format!("{} is now counted in {}, past amounts were converted from {}", name, value, old))
+            }
+            "unit_size" | "size" => {
+                let size = value.parse::<f64>().ok().filter(|n| n.is_finite() && *n > 0.0)
+                    .ok_or(format!("Unit size must be a number above 0, got '{}'", value))?;
+                *unit_size = size;
+                Ok(format!("Logging 1 for {} now adds {}{}, past days are unchanged", name, units::number(size), unit))
+            }
+            _ => Err(format!("Can't edit '{}'. Try unit, unit_size or display", field)),
+        }
+    }
+    // Swaps the unit's name without touching any amount, for units that
+    // can't be converted. The caller should make sure that's meant
+    pub fn relabel_unit(&mut self, value: &str) -> Result<String, String>{
+        if value.is_empty() || value.contains(char::is_whitespace) {
+            return Err(format!("'{}' is not a unit", value));
+        }
+        let (name, unit) = match self {
+            Habit::Quantity(q) => (&q.name, &mut q.unit),
+            Habit::Limit(l) => (&l.name, &mut l.unit),
+            _ => return Err("Only quantity and limit habits have a unit to edit".to_string()),
+        };
+        *unit = value.to_string();
+        Ok(format!("{} is now counted in {}, past amounts keep their numbers", name, value))
+    }
+    pub fn unit(&self) -> Option<&str>{
+        match self {
+            Habit::Quantity(q) => Some(&q.unit),
+            Habit::Limit(l) => Some(&l.unit),
+            _ => None,
+        }
+    }
+    // Puts every amount, limit, goal and record into a unit `factor` times smaller
+    fn scale(&mut self, factor: f64){
+        match self {
+            Habit::Quantity(q) => {
+                q.log.scale(factor);
+                q.unit_size *= factor;
+                q.target = q.target.map(|t| t.scale(factor));
+            }
+            Habit::Limit(l) => {
+                l.log.scale(factor);
+                l.unit_size *= factor;
+                l.max = units::round(l.max * factor);
+            }
+            _ => return,
+        }
+        let records = self.records_mut();
+        for best in [&mut records.day, &mut records.week].into_iter().flatten() {
+            best.value = units::round(best.value * factor);
+        }
+    }
+    pub fn has_tag(&self, tag: &str) -> bool{
+        self.tags().contains(tag)
+    }
+    // Whether `list` and `stats` take this habit in, for an optional tag
+    // and either the archived habits or the others
+    pub fn is_shown(&self, tag: Option<&str>, archived: bool) -> bool{
+        self.is_archived() == archived && tag.is_none_or(|t| self.has_tag(t))
+    }
+    pub fn tag(&mut self, tags: &[&str]) -> Result<String, String>{
+        if let Some(bad) = tags.iter().find(|t| !is_valid_habit_name(t)) {
+            return Err(format!("'{}' is not a valid tag. Use lowercase letters, numbers and dashes", bad));
+        }
+        let added: Vec<&str> = tags.iter().copied()
+            .filter(|t| self.filing_mut().tags.insert(t.to_string()))
+            .collect();
+        if added.is_empty() {
+            return Err(format!("{} already has those tags", self.name()));
+        }
+        Ok(format!("Tagged {} with {}", self.name(), added.join(", ")))
+    }
+    pub fn untag(&mut self, tags: &[&str]) -> Result<String, String>{
+        let removed: Vec<&str> = tags.iter().copied()
+            .filter(|t| self.filing_mut().tags.remove(*t))
+            .collect();
+        if removed.is_empty() {
+            return Err(format!("{} has none of those tags", self.name()));
+        }
+        Ok(format!("Removed {} from {}", removed.join(", "), self.name()))
+    }
+    // Every run the habit has had, oldest first
+    fn runs(&self, today: NaiveDate) -> Vec<Run>{
+        match self {
+            Habit::Streak(s) => s.runs(today),
+            Habit::Quantity(q) if q.target.is_some() => Schedule::Daily.runs(&q.met_days(), &q.excused.days(), today),
+            Habit::Limit(l) => l.runs(today),
+            Habit::Abstinence(a) => a.runs(today),
+            Habit::Checklist(c) => c.schedule.runs(&c.completed_days(), &c.excused.days(), today),
+            Habit::Quantity(_) | Habit::Timed(_) => Vec::new(),
+        }
+    }
+    // Amounts worth a best day and best week
+    fn totals(&self) -> Option<&DayLog>{
+        match self {
+            Habit::Quantity(q) => Some(&q.log),
+            Habit::Timed(t) => Some(&t.log),
+            _ => None,
+        }
+    }
+    // Brings the records up to date, returning which ones were just beaten
+    fn update_records(&mut self, today: NaiveDate) -> Vec<String>{
+        let longest = self.runs(today).into_iter().reduce(|a, b| if b.count > a.count { b } else { a });
+        let log = self.totals().cloned();
+        let records = self.records_mut();
+        let mut beaten = Vec::new();
+        if records.update_streak(longest) && let Some(run) = records.streak {
+            beaten.push(format!("streak of {} days", run.count));
+        }
+        let totals = log.map(|log| records.update_totals(&log)).unwrap_or_default();
+        for (period, best) in totals {
+            beaten.push(format!("{} with {}", period, self.show_amount(best.value)));
+        }
+        beaten
+    }
+    // An amount of this habit, in its display unit
+    pub fn show_amount(&self, value: f64) -> String{
+        match self {
+            Habit::Quantity(q) => q.show(value),
+            Habit::Limit(l) => l.show(value),
+            _ => format!("{}min", units::number(value)),
+        }
+    }
+    fn note_records(&mut self, today: NaiveDate, message: String) -> String{
+        let beaten = self.update_records(today);
+        if beaten.is_empty() {
+            message
+        } else {
+            format!("{} - 🏆 New personal best {}!", message, beaten.join(", "))
+        }
+    }
+    // Days that don't count against the streak, for habits that keep one
+    pub fn excused(&self) -> Option<&Excused>{
+        match self {
+            Habit::Streak(s) => Some(&s.excused),
+            Habit::Quantity(q) => q.target.map(|_| &q.excused),
+            Habit::Limit(l) => Some(&l.excused),
+            Habit::Checklist(c) => Some(&c.excused),
+            Habit::Abstinence(_) | Habit::Timed(_) => None,
+        }
+    }
+    fn excused_mut(&mut self) -> Option<&mut Excused>{
+        match self {
+            Habit::Streak(s) => Some(&mut s.excused),
+            Habit::Quantity(q) => q.target.map(|_| &mut q.excused),
+            Habit::Limit(l) => Some(&mut l.excused),
+            Habit::Checklist(c) => Some(&mut c.excused),
+            Habit::Abstinence(_) | Habit::Timed(_) => None,
+        }
+    }
+    // Days that counted towards the streak, what freeze tokens are earned from
+    fn successes(&self, today: NaiveDate) -> u32{
+        match self {
+            Habit::Streak(s) => s.completions.len() as u32,
+            Habit::Quantity(q) => q.met_days().len() as u32,
+            Habit::Limit(l) => l.runs(today).iter().map(|r| r.count).sum(),
+            Habit::Checklist(c) => c.completed_days().len() as u32,
+            Habit::Abstinence(_) | Habit::Timed(_) => 0,
+        }
+    }
+    fn succeeded_on(&self, date: NaiveDate) -> bool{
+        match self {
+            Habit::Streak(s) => s.completions.contains(&date),
+            Habit::Quantity(q) => q.met_days().contains(&date),
+            Habit::Limit(l) => !l.over_days().contains(&date),
+            Habit::Checklist(c) => c.is_complete_on(date),
+            Habit::Abstinence(_) | Habit::Timed(_) => false,
+        }
+    }
+    pub fn freezes_available(&self, today: NaiveDate) -> u32{
+        self.excused().map_or(0, |e| e.freezes_available(self.successes(today)))
+    }
+    // Marks a day as skipped or frozen so it can't break the streak
+    pub fn excuse(&mut self, date: NaiveDate, excuse: Excuse, clock: &dyn Clock) -> Result<String, String>{
+        let today = clock.today();
+        if excuse == Excuse::Freeze {
+            if date > today {
+                return Err("Freezes can only cover today or days gone by".to_string());
+            }
+            if self.succeeded_on(date) {
+                return Err(format!("{} doesn't need a freeze on {}", self.name(), date));
+            }
+            if self.freezes_available(today) == 0 {
+                return Err(format!("No freezes left. You earn one every {} successful days", FREEZE_EVERY));
+            }
+        }
+        let name = self.name().to_string();
+        let excused = self.excused_mut()
+            .ok_or(format!("{} doesn't keep a streak to protect", name))?;
+        excused.insert(date, excuse);
+        self.refresh_streak(today);
+        Ok(format!("{} {} on {}", name, excuse, date))
+    }
+    // Replaces any vacation days with the given ranges
+    pub fn apply_vacations(&mut self, vacations: &[Vacation], today: NaiveDate){
+        let Some(excused) = self.excused_mut() else { return };
+        excused.clear_vacations();
+        for day in vacations.iter().flat_map(|v| v.days()) {
+            excused.insert(day, Excuse::Vacation);
+        }
+        self.refresh_streak(today);
+    }
+    // For a new habit, so vacations already planned cover it too
+    pub fn on_vacations(mut self, vacations: &[Vacation], today: NaiveDate) -> Self{
+        self.apply_vacations(vacations, today);
+        self
+    }
+    pub fn complete(&mut self, annotation: Annotation, clock: &dyn Clock) -> Result<String, String>{
+        let today = clock.today();
+        let result = match self {
+            Habit::Streak(streak_habit) => {
+                streak_habit.mark_complete(today);
+                streak_habit.annotate(today, annotation);
+                Ok(format!("Streak: {} days", streak_habit.streak))
+            },
+            Habit::Checklist(checklist) => {
+                checklist.check_all(today, today);
+                checklist.annotate(today, annotation);
+                Ok(format!("All {} items done - Streak: {} days", checklist.items.len(), checklist.streak))
+            },
+            Habit::Quantity(_) | Habit::Limit(_) => {
+                Err("Cannot complete quantity habit. Use 'log <amount>' instead".to_string())            },
+            Habit::Abstinence(_) => {
+                Err("Abstinence habits count clean days on their own. Use 'relapse' if you slipped".to_string())
+            },
+            Habit::Timed(_) => {
+                Err("Cannot complete timed habit. Use 'start' and 'stop' instead".to_string())
+            },
+        };
+        result.map(|msg| self.note_records(today, msg))
+    }
+    // complete for a past day
+    pub fn complete_on(&mut self, date: NaiveDate, annotation: Annotation, clock: &dyn Clock) -> Result<String, String>{
+        let today = clock.today();
+        let result = match self {
+            Habit::Streak(streak_habit) => {
+                streak_habit.complete_on(date, today)?;
+                streak_habit.annotate(date, annotation);
+                Ok(format!("Backfilled {} - Streak: {} days, longest {} days",
+                    date, streak_habit.streak, streak_habit.longest_streak(today)))
+            },
+            Habit::Checklist(checklist) => {
+                if date > today {
+                    return Err(format!("Cannot complete {} ahead of time", date));
+                }
+                checklist.check_all(date, today);
+                checklist.annotate(date, annotation);
+                Ok(format!("Backfilled {} - Streak: {} days", date, checklist.streak))
+            },
+            Habit::Quantity(_) | Habit::Limit(_) => {
+                Err("Cannot complete quantity habit. Use 'log <amount>' instead".to_string())            },
+            Habit::Abstinence(_) => {
+                Err("Abstinence habits count clean days on their own. Use 'relapse' if you slipped".to_string())
+            },
+            Habit::Timed(_) => {
+                Err("Cannot complete timed habit. Use 'start' and 'stop' instead".to_string())
+            },
+        };
+        result.map(|msg| self.note_records(today, msg))
+    }
+    pub fn set_schedule(&mut self, schedule: Schedule, clock: &dyn Clock) -> Result<String, String>{
+        let today = clock.today();
+        match self{
+            Habit::Streak(streak_habit) => {
+                streak_habit.set_schedule(schedule, today);
+                Ok(format!("{} is now {} - Streak: {}", streak_habit.name, streak_habit.schedule, streak_habit.streak))
+            },
+            Habit::Checklist(checklist) => {
+                checklist.schedule = schedule;
+                checklist.recompute_streak(today);
+                Ok(format!("{} is now {} - Streak: {}", checklist.name, checklist.schedule, checklist.streak))
+            },
+            Habit::Quantity(_) | Habit::Limit(_) | Habit::Abstinence(_) | Habit::Timed(_) => {
+                Err("Schedules only apply to streak and checklist habits".to_string())
+            },
+        }
+    }
+    pub fn set_target(&mut self, target: Option<Target>) -> Result<String, String>{
+        match self{
+            Habit::Quantity(q) => {
+                q.set_target(target);
+                Ok(match target {
+                    Some(t) => format!("{} goal is now {} a day - Streak: {}", q.name, t.describe(|v| q.show(v)), q.streak),
+                    None => format!("{} no longer has a goal", q.name),
+                })
+            },
+            Habit::Limit(_) => {
+                Err("Limit habits already have a daily maximum".to_string())
+            },
+            Habit::Streak(_) | Habit::Abstinence(_) | Habit::Timed(_) | Habit::Checklist(_) => {
+                Err("Targets only apply to quantity habits".to_string())
+            },
+        }
+    }
+    // complete for quantity. Amounts with a unit are converted to the
+    // habit's own, a bare number counts steps of unit_size. A negative
+    // amount takes some back, as long as today stays at 0 or more
+    pub fn log_amount(&mut self, amount: Amount, annotation: Annotation, clock: &dyn Clock) -> Result<String, String> {
+        self.roll_over(clock.today());
+        let stored = |unit: &str, unit_size: f64, log: &DayLog| -> Result<f64, String> {
+            let value = units::round(amount.in_unit(unit, unit_size)?);
+            if units::round(log.today_total + value) < 0.0 {
+                return Err("That would take today's total below 0".to_string());
+            }
+            Ok(value)
+        };
+        let result = match self {
+            Habit::Quantity(q) => {
+                q.log(stored(&q.unit, q.unit_size, &q.log)?, annotation, clock);
+                Ok(format!("Logged {}", q.show(q.log.today_total)))
+            }
+            Habit::Limit(l) => {
+                l.log(stored(&l.unit, l.unit_size, &l.log)?, annotation, clock);
+                if l.over_today() {
+                    Ok(format!("Logged {} - over your limit of {}", l.show(l.log.today_total), l.show(l.max)))
+                } else {
+                    Ok(format!("Logged {} of {}", l.show(l.log.today_total), l.show(l.max)))
+                }
+            }
+            Habit::Streak(_) => {
+                Err("Cannot log quantity for streak habit. Use 'complete' instead".to_string())
+            }
+            Habit::Checklist(_) => {
+                Err("Cannot log quantity for checklist habit. Use 'check' instead".to_string())
+            }
+            Habit::Abstinence(_) => {
+                Err("Cannot log quantity for abstinence habit. Use 'relapse' instead".to_string())
+            }
+            // Lets a forgotten session be added by hand, in minutes
+            Habit::Timed(t) => {
+                t.log.record(stored("min", 1.0, &t.log)?, annotation, clock, |_| None);
+                Ok(format!("Logged {}min", units::number(t.log.today_total)))
+            }
+        };
+        if amount.value < 0.0 {
+            self.corrected(clock.today());
+            return result;
+        }
+        result.map(|msg| self.note_records(clock.today(), msg))
+    }
+    // The log that single entries can be corrected in. Timed habits are left
+    // out since their minutes come from sessions
+    fn entry_log(&mut self) -> Result<&mut DayLog, String>{
+        match self {
+            Habit::Quantity(q) => Ok(&mut q.log),
+            Habit::Limit(l) => Ok(&mut l.log),
+            _ => Err("Only quantity and limit habits keep entries to correct".to_string()),
+        }
+    }
+    // Today's entries, numbered the way delete_entry and edit_entry take them
+    pub fn entries(&mut self, clock: &dyn Clock) -> Result<Vec<String>, String>{
+        self.roll_over(clock.today());
+        let log = self.entry_log()?.clone();
+        Ok(log.today_history.iter().enumerate().map(|(i, entry)| {
+            let time = clock.home().home_time(entry.at).format("%H:%M");
+            let line = format!("{}. {} {}", i + 1, time, self.show_amount(entry.value));
+            if entry.annotation.is_empty() { line } else { format!("{} · {}", line, entry.annotation) }
+        }).collect())
+    }
+    pub fn delete_entry(&mut self, number: usize, clock: &dyn Clock) -> Result<String, String>{
+        self.roll_over(clock.today());
+        let entry = self.entry_log()?.remove_entry(number)?;
+        self.corrected(clock.today());
+        let total = self.entry_log()?.today_total;
+        Ok(format!("Removed entry {} ({}) from {}, {} today",
+            number, self.show_amount(entry.value), self.name(), self.show_amount(total)))
+    }
+    // Sets an entry to `amount`, read the same way as for log
+    pub fn edit_entry(&mut self, number: usize, amount: Amount, clock: &dyn Clock) -> Result<String, String>{
+        self.roll_over(clock.today());
+        let value = match self {
+            Habit::Quantity(q) => amount.in_unit(&q.unit, q.unit_size)?,
+            Habit::Limit(l) => amount.in_unit(&l.unit, l.unit_size)?,
+            _ => return Err("Only quantity and limit habits keep entries to correct".to_string()),
+        };
+        let value = units::round(value);
+        let old = self.entry_log()?.amend_entry(number, value)?;
+        self.corrected(clock.today());
+        let total = self.entry_log()?.today_total;
+        Ok(format!("Entry {} of {} is now {} (was {}), {} today",
+            number, self.name(), self.show_amount(value), self.show_amount(old), self.show_amount(total)))
+    }
+    // After a correction the day may no longer meet its target, and a
+    // mistyped amount shouldn't linger as a best day or week
+    fn corrected(&mut self, today: NaiveDate){
+        match self {
+            Habit::Quantity(q) => q.recompute_streak(),
+            Habit::Limit(l) => l.recompute_streak(today),
+            _ => (),
+        }
+        let records = self.records_mut();
+        records.day = None;
+        records.week = None;
+        self.update_records(today);
+    }
+    // Ticks (or unticks) one item of a checklist, today unless `date` is given
+    pub fn check_item(&mut self, item: &str, date: Option<NaiveDate>, checked: bool, annotation: Annotation, clock: &dyn Clock) -> Result<String, String>{
+        let today = clock.today();
+        let date = date.unwrap_or(today);
+        let result = match self {
+            Habit::Checklist(c) => {
+                if checked {
+                    c.check(item, date, today)?;
+                    c.annotate(date, annotation);
+                } else {
+                    c.uncheck(item, date, today)?;
+                }
+                let status = if c.is_complete_on(date) { " - done for the day" } else { "" };
+                Ok(format!("{}/{} items on {}{} - Streak: {} days",
+                    c.checked_on(date), c.items.len(), date, status, c.streak))
+            }
+            _ => Err("Only checklist habits have items".to_string()),
+        };
+        result.map(|msg| self.note_records(today, msg))
+    }
+    pub fn set_rule(&mut self, rule: CompletionRule, clock: &dyn Clock) -> Result<String, String>{
+        let today = clock.today();
+        match self {
+            Habit::Checklist(c) => {
+                c.set_rule(rule, today);
+                Ok(format!("{} now needs {} of {} items - Streak: {} days", c.name, c.required(), c.items.len(), c.streak))
+            }
+            _ => Err("Only checklist habits have a completion rule".to_string()),
+        }
+    }
+    pub fn start_timer(&mut self, clock: &dyn Clock) -> Result<String, String>{
+        let now = clock.now();
+        match self {
+            Habit::Timed(t) => {
+                t.start(clock)?;
+                Ok(format!("Timer started for {} at {}", t.name, now.format("%H:%M")))
+            }
+            _ => Err("Only timed habits have a timer. Add one with 'timed <name>'".to_string()),
+        }
+    }
+    pub fn stop_timer(&mut self, annotation: Annotation, clock: &dyn Clock) -> Result<String, String>{
+        let result = match self {
+            Habit::Timed(t) => {
+                let session = t.stop(clock, annotation)?;
+                Ok(format!("{} session of {}min, {}min today", t.name, session.minutes(), units::number(t.log.today_total)))
+            }
+            _ => Err("Only timed habits have a timer. Add one with 'timed <name>'".to_string()),
+        };
+        result.map(|msg| self.note_records(clock.today(), msg))
+    }
+    pub fn relapse(&mut self, note: Option<String>, clock: &dyn Clock) -> Result<String, String>{
+        match self {
+            Habit::Abstinence(a) => {
+                let run = a.streak;
+                a.relapse(clock, note);
+                Ok(format!("Relapse logged after {} days clean. Starting again from today", run))
+            }
+            _ => Err("Only abstinence habits can relapse".to_string()),
+        }
+    }
+    // Every note and rating on this habit with its day, oldest first
+    pub fn annotations(&self, home: Home) -> Vec<(NaiveDate, Annotation)>{
+        let mut found = match self {
+            Habit::Streak(s) => s.notes.iter().map(|(d, a)| (*d, a.clone())).collect(),
+            Habit::Checklist(c) => c.notes.iter().map(|(d, a)| (*d, a.clone())).collect(),
+            Habit::Quantity(q) => q.log.annotations(),
+            Habit::Limit(l) => l.log.annotations(),
+            Habit::Timed(t) => {
+                let sessions = t.sessions.iter()
+                    .filter(|s| !s.annotation.is_empty())
+                    .map(|s| (home.day_of(s.start), s.annotation.clone()));
+                sessions.chain(t.log.annotations()).collect()
+            }
+            Habit::Abstinence(a) => a.relapses.iter()
+                .filter_map(|r| r.note.clone().map(|note| (r.day, Annotation { note: Some(note), rating: None })))
+                .collect(),
+        };
+        found.sort_by_key(|(d, _)| *d);
+        found
+    }
+}
+
+// Habits in undo snapshots from before IDs existed take the identity of
+// the loaded habit with the same name, so they stay the same habit
+pub fn adopt_ids(habits: &mut [Habit], loaded: &[Habit]){
+    for habit in habits.iter_mut().filter(|h| h.id().is_empty()) {
+        if let Some(known) = loaded.iter().find(|h| h.name() == habit.name()) {
+            *habit.identity_mut() = known.identity().clone();
+        }
+    }
+}
+
+// Gives habits from older files, and any that share an ID after files
+// were combined, an ID of their own
+pub fn assign_ids(habits: &mut [Habit], clock: &dyn Clock){
+    let mut seen: BTreeSet<String> = BTreeSet::new();
+    for habit in habits.iter_mut() {
+        if habit.identity().created.is_none() {
+            let created = habit.first_day()
+                .and_then(|day| day.and_hms_opt(0, 0, 0))
+                .map_or_else(|| clock.now(), |day| clock.home().at_home_time(day));
+            habit.identity_mut().created = Some(created);
+        }
+        while habit.id().is_empty() || seen.contains(habit.id()) {
+            habit.identity_mut().id = new_id();
+        }
+        seen.insert(habit.id().to_string());
+    }
+}
+
+// Shared behaviours
+pub trait HabitFunctions {
+    fn name(&self)->&str;
+    fn reset(&mut self, clock: &dyn Clock) -> Result<String, String>;
+    fn display_line(&self, clock: &dyn Clock) -> String;
+    // As of `today`, None for habits that don't keep a streak
+    fn streak(&self, today: NaiveDate) -> Option<u32>;
+    // Extra lines for `view` and the TUI detail pane
+    fn details(&self, clock: &dyn Clock) -> Vec<String>;
+}
+
+impl HabitFunctions for Habit{
+    fn name(&self)->&str {
+        match self{
+            Habit::Streak(h) => &h.name,
+            Habit::Quantity(q) => &q.name,
+            Habit::Limit(l) => &l.name,
+            Habit::Abstinence(a) => &a.name,
+            Habit::Timed(t) => &t.name,
+            Habit::Checklist(c) => &c.name,
+        }
+    }
+
+    // Takes back today's progress only, earlier days are history
+    fn reset(&mut self, clock: &dyn Clock) -> Result<String, String> {
+        let today = clock.today();
+        match self {
+            Habit::Streak(streak_habit) => {
+                streak_habit.completions.remove(&today);
+                streak_habit.notes.remove(&today);
+                streak_habit.recompute_streak(today);
+            },
+            Habit::Quantity(quantity_habit) => {
+                quantity_habit.log.clear_today();
+                quantity_habit.recompute_streak();
+            },
+            Habit::Limit(limit_habit) => {
+                limit_habit.log.clear_today();
+                limit_habit.recompute_streak(today);
+            },
+            // Today's relapses are what there is to take back
+            Habit::Abstinence(abstinence_habit) => {
+                abstinence_habit.relapses.retain(|r| r.day != today);
+                abstinence_habit.recompute_streak(today);
+            },
+            // The running session would be lost along with today's
+            Habit::Timed(timed_habit) => {
+                if timed_habit.running.is_some() {
+                    return Err(format!("{} is running. Use 'stop {}' first", timed_habit.name, timed_habit.name));
+                }
+                let home = clock.home();
+                timed_habit.sessions.retain(|s| home.day_of(s.end) != today);
+                timed_habit.log.clear_today();
+            },
+            Habit::Checklist(checklist) => {
+                for item in &mut checklist.items {
+                    item.completions.remove(&today);
+                }
+                checklist.notes.remove(&today);
+                checklist.recompute_streak(today);
+            },
+        }
+        Ok(format!("Reset today for {}", self.name()))
+    }
+    
+    fn display_line(&self, clock: &dyn Clock) -> String {
+        let today = clock.today();
+        let (streak, status) = self.streak_status(today).unwrap_or((0, StreakStatus::Current));
+        let line = match self{
+            Habit::Streak(s) => {
+                if s.schedule.is_daily() {
+                    format!("[S] {}: streak {}",s.name,streak)
+                } else {
+                    format!("[S] {} ({}): streak {}",s.name,s.schedule,streak)
+                }
+            },
+            Habit::Quantity(q) => {
+                match q.target {
+                    Some(t) => {
+                        let mark = if q.today_met() == Some(true) { "✓" } else { " " };
+                        format!("[Q] {}: {} today {} (goal {}, streak {})",
+                            q.name, q.show(q.log.today_total), mark, t.describe(|v| q.show(v)), streak)
+                    }
+                    None => format!("[Q] {}: {} today",q.name, q.show(q.log.today_total)),
+                }
+            },
+            Habit::Limit(l) => {
+                if l.over_today() {
+                    format!("[L] {}: {}/{} today ✗ over limit", l.name, units::number(l.log.today_total), l.show(l.max))
+                } else {
+                    format!("[L] {}: {}/{} today, clean {} days", l.name, units::number(l.log.today_total), l.show(l.max), streak)
+                }
+            },
+            Habit::Abstinence(a) => {
+                format!("[A] {}: {} days clean", a.name, streak)
+            },
+            Habit::Timed(t) => {
+                match t.elapsed_minutes(clock.now()) {
+                    Some(elapsed) => format!("[T] {}: {}min today ⏱ running {}min", t.name, units::number(t.log.today_total), elapsed),
+                    None => format!("[T] {}: {}min today", t.name, units::number(t.log.today_total)),
+                }
+            },
+            Habit::Checklist(c) => {
+                let mark = if c.is_complete_on(today) { "✓" } else { " " };
+                format!("[C] {}: {}/{} today {} (streak {})", c.name, c.checked_on(today), c.items.len(), mark, streak)
+            },
+        };
+        let line = match status {
+            StreakStatus::Current => line,
+            StreakStatus::AtRisk => format!("{} · ⚠ {}", line, status),
+            StreakStatus::Broken => format!("{} · ✗ {}", line, status),
+        };
+        match self.excused().and_then(|e| e.get(today)) {
+            Some(excuse) => format!("{} · {} today", line, excuse),
+            None => line,
+        }
+    }
+
+    fn streak(&self, today: NaiveDate) -> Option<u32> {
+        self.streak_status(today).map(|(count, _)| count)
+    }
+
+    fn details(&self, clock: &dyn Clock) -> Vec<String> {
+        let mut lines = Vec::new();
+        if let Habit::Checklist(c) = self {
+            let today = clock.today();
+            for item in &c.items {
+                let mark = if item.completions.contains(&today) { "x" } else { " " };
+                lines.push(format!("[{}] {} ({} days total)", mark, item.name, item.completions.len()));
+            }
+        }
+        let identity = self.identity();
+        match identity.created {
+            Some(created) => lines.push(format!("ID {} · created {}", identity.id, clock.home().home_time(created).format("%Y-%m-%d %H:%M"))),
+            None => lines.push(format!("ID {}", identity.id)),
+        }
+        if let Some(since) = self.archived() {
+            lines.push(format!("📦 Archived since {}", since));
+        }
+        if !self.tags().is_empty() {
+            lines.push(format!("🏷 {}", self.tags().iter().join(", ")));
+        }
+        let records = self.records();
+        if let Some(run) = records.streak {
+            lines.push(format!("🏆 Best streak {} days ({} to {})", run.count, run.start, run.end));
+        }
+        if let Some(best) = records.day {
+            lines.push(format!("🏆 Best day {} on {}", self.show_amount(best.value), best.date));
+        }
+        if let Some(best) = records.week {
+            lines.push(format!("🏆 Best week {} from {}", self.show_amount(best.value), best.date));
+        }
+        if let Some(excused) = self.excused() {
+            let today = clock.today();
+            lines.push(format!("❄ {} freezes available", self.freezes_available(today)));
+            for (date, excuse) in excused.iter().rev().take(10) {
+                lines.push(format!("{}: {}", date, excuse));
+            }
+        }
+        // Most recent notes first
+        for (date, annotation) in self.annotations(clock.home()).iter().rev().take(10) {
+            lines.push(format!("{}: {}", date, annotation));
+        }
+        lines
+    }
+}
+#[cfg(test)]
+mod tests {
+    use chrono::TimeDelta;
+
+    use super::*;
+    use crate::clock::FixedClock;
+
+    fn date(s: &str) -> NaiveDate{
+        s.parse().unwrap()
+    }
+
+    // Completes `habit` once a day, starting at `clock`, for `days` days
+    fn complete_daily(habit: &mut Habit, clock: &FixedClock, days: u32){
+        for _ in 0..days {
+            habit.complete(Annotation::default(), clock).unwrap();
+            clock.advance(TimeDelta::days(1));
+        }
+    }
+
+    #[test]
+    fn streak_continues_across_midnight(){
+        let clock = FixedClock::at("2024-03-05 23:59");
+        let mut habit = Habit::new_streak("read".to_string(), &clock);
+        habit.complete(Annotation::default(), &clock).unwrap();
+        clock.advance(TimeDelta::minutes(2));
+        assert_eq!(clock.today(), date("2024-03-06"));
+        habit.complete(Annotation::default(), &clock).unwrap();
+        assert_eq!(habit.streak(clock.today()), Some(2));
+    }
+
+    #[test]
+    fn completing_twice_before_midnight_counts_once(){
+        let clock = FixedClock::at("2024-03-05 00:00");
+        let mut habit = Habit::new_streak("read".to_string(), &clock);
+        habit.complete(Annotation::default(), &clock).unwrap();
+        clock.advance(TimeDelta::minutes(23 * 60 + 59));
+        habit.complete(Annotation::default(), &clock).unwrap();
+        assert_eq!(habit.streak(clock.today()), Some(1));
+    }
+
+    #[test]
+    fn streak_continues_across_month_end(){
+        let clock = FixedClock::at("2024-01-30 12:00");
+        let mut habit = Habit::new_streak("read".to_string(), &clock);
+        complete_daily(&mut habit, &clock, 3);
+        assert_eq!(habit.streak(clock.today()), Some(3));
+        let Habit::Streak(s) = &habit else { unreachable!() };
+        assert_eq!(s.last_completed(), Some(date("2024-02-01")));
+    }
+
+    #[test]
+    fn leap_day_is_a_day_to_keep(){
+        let clock = FixedClock::at("2024-02-28 08:00");
+        let mut habit = Habit::new_streak("read".to_string(), &clock);
+        complete_daily(&mut habit, &clock, 3);
+        assert_eq!(habit.streak(clock.today()), Some(3));
+
+        // Missing 29 February breaks the streak
+        let clock = FixedClock::at("2024-02-28 08:00");
+        let mut habit = Habit::new_streak("read".to_string(), &clock);
+        habit.complete(Annotation::default(), &clock).unwrap();
+        clock.advance(TimeDelta::days(2));
+        assert_eq!(clock.today(), date("2024-03-01"));
+        habit.complete(Annotation::default(), &clock).unwrap();
+        assert_eq!(habit.streak(clock.today()), Some(1));
+    }
+
+    #[test]
+    fn february_ends_on_the_28th_outside_leap_years(){
+        let clock = FixedClock::at("2023-02-28 08:00");
+        let mut habit = Habit::new_streak("read".to_string(), &clock);
+        complete_daily(&mut habit, &clock, 2);
+        assert_eq!(habit.streak(clock.today()), Some(2));
+    }
+
+    #[test]
+    fn weekly_schedule_spans_month_end(){
+        // Wednesday 31 January and Thursday 1 February share a week
+        let clock = FixedClock::at("2024-01-31 08:00");
+        let mut habit = Habit::new_streak("gym".to_string(), &clock);
+        habit.set_schedule(Schedule::TimesPerWeek(2), &clock).unwrap();
+        complete_daily(&mut habit, &clock, 2);
+        assert_eq!(habit.streak(clock.today()), Some(2));
+    }
+
+    #[test]
+    fn quantity_log_after_midnight_starts_a_new_day(){
+        let clock = FixedClock::at("2024-01-31 23:30");
+        let mut habit = Habit::new_quantity("water".to_string(), "ml".to_string(), 1.0, &clock);
+        habit.log_amount(500.into(), Annotation::default(), &clock).unwrap();
+        clock.advance(TimeDelta::hours(1));
+        habit.log_amount(250.into(), Annotation::default(), &clock).unwrap();
+        let Habit::Quantity(q) = &habit else { unreachable!() };
+        assert_eq!(q.log.today_date, date("2024-02-01"));
+        assert_eq!(q.log.history.len(), 1);
+        assert_eq!(q.log.history[0].date, date("2024-01-31"));
+        assert_eq!(q.log.history[0].value, 500.0);
+        assert_eq!(q.log.today_total, 250.0);
+    }
+
+    #[test]
+    fn days_without_logs_are_archived_as_zero(){
+        let clock = FixedClock::at("2024-02-28 20:00");
+        let mut habit = Habit::new_quantity("water".to_string(), "ml".to_string(), 1.0, &clock);
+        habit.set_target(Some(Target::AtLeast(100.0))).unwrap();
+        habit.log_amount(500.into(), Annotation::default(), &clock).unwrap();
+        clock.advance(TimeDelta::days(3));
+        habit.log_amount(200.into(), Annotation::default(), &clock).unwrap();
+        let Habit::Quantity(q) = &habit else { unreachable!() };
+        let days: Vec<(NaiveDate, f64, Option<bool>)> = q.log.history.iter().map(|e| (e.date, e.value, e.met)).collect();
+        assert_eq!(days, vec![
+            (date("2024-02-28"), 500.0, Some(true)),
+            (date("2024-02-29"), 0.0, Some(false)),
+            (date("2024-03-01"), 0.0, Some(false)),
+        ]);
+        assert_eq!(q.log.today_date, date("2024-03-02"));
+        assert_eq!(q.streak, 1);
+    }
+
+    #[test]
+    fn loading_rolls_a_stale_day_over_and_fills_gaps(){
+        let mut log = DayLog::new(date("2025-12-30"));
+        log.today_total = 300.0;
+        log.history.push(DailyEntry { date: date("2025-12-23"), value: 100.0, met: None, notes: Vec::new() });
+        log.history.push(DailyEntry { date: date("2025-12-28"), value: 200.0, met: None, notes: Vec::new() });
+        let mut habit = Habit::Limit(LimitHabit { name: "coffee".to_string(), unit: "cups".to_string(), unit_size: 1.0,
+                                                  max: 250.0, log, streak: 0, excused: Excused::default(),
+                                                  records: Records::default(), filing: Filing::default(), identity: Identity::default() });
+        habit.migrate(date("2026-01-02"));
+        let Habit::Limit(l) = &habit else { unreachable!() };
+        assert_eq!(l.log.today_total, 0.0);
+        assert_eq!(l.log.today_date, date("2026-01-02"));
+        assert_eq!(l.log.history.len(), 10);
+        assert!(l.log.history.windows(2).all(|w| w[1].date == w[0].date.succ_opt().unwrap()));
+        assert_eq!(l.over_days(), BTreeSet::from([date("2025-12-30")]));
+        assert_eq!(l.streak, 3);
+    }
+
+    #[test]
+    fn clean_days_count_leap_day(){
+        let clock = FixedClock::at("2024-03-01 09:00");
+        let habit = Habit::new_abstinence("smoking".to_string(), date("2024-02-01"), &clock);
+        assert_eq!(habit.streak(clock.today()), Some(29));
+    }
+
+    #[test]
+    fn relapse_just_before_midnight_belongs_to_that_day(){
+        let clock = FixedClock::at("2024-02-29 23:59");
+        let mut habit = Habit::new_abstinence("smoking".to_string(), date("2024-02-01"), &clock);
+        habit.relapse(None, &clock).unwrap();
+        clock.advance(TimeDelta::minutes(2));
+        habit.refresh_streak(clock.today());
+        let Habit::Abstinence(a) = &habit else { unreachable!() };
+        assert_eq!(a.clean_since(), date("2024-02-29"));
+        assert_eq!(a.streak, 1);
+    }
+
+    #[test]
+    fn timer_running_past_midnight_keeps_its_length(){
+        let clock = FixedClock::at("2024-12-31 23:30");
+        let mut habit = Habit::new_timed("piano".to_string(), &clock);
+        habit.start_timer(&clock).unwrap();
+        clock.advance(TimeDelta::hours(1));
+        habit.stop_timer(Annotation::default(), &clock).unwrap();
+        let Habit::Timed(t) = &habit else { unreachable!() };
+        assert_eq!(t.sessions[0].minutes(), 60);
+        assert_eq!(t.log.today_date, date("2025-01-01"));
+    }
+
+    #[test]
+    fn best_streak_outlives_a_broken_run(){
+        let clock = FixedClock::at("2024-01-01 08:00");
+        let mut habit = Habit::new_streak("read".to_string(), &clock);
+        complete_daily(&mut habit, &clock, 5);
+        clock.advance(TimeDelta::days(3));
+        complete_daily(&mut habit, &clock, 2);
+        let best = habit.records().streak.unwrap();
+        assert_eq!((best.count, best.start, best.end), (5, date("2024-01-01"), date("2024-01-05")));
+        assert_eq!(habit.streak(clock.today()), Some(2));
+    }
+
+    #[test]
+    fn beating_a_record_says_so(){
+        let clock = FixedClock::at("2024-01-01 08:00");
+        let mut habit = Habit::new_streak("read".to_string(), &clock);
+        complete_daily(&mut habit, &clock, 2);
+        clock.advance(TimeDelta::days(2));
+        complete_daily(&mut habit, &clock, 2);
+        let message = habit.complete(Annotation::default(), &clock).unwrap();
+        assert!(message.contains("New personal best streak of 3 days"), "{}", message);
+    }
+
+    #[test]
+    fn best_day_and_week_follow_the_log(){
+        let clock = FixedClock::at("2024-02-26 08:00");
+        let mut habit = Habit::new_quantity("water".to_string(), "ml".to_string(), 1.0, &clock);
+        let first = habit.log_amount(1000.into(), Annotation::default(), &clock).unwrap();
+        assert!(!first.contains("personal best"));
+        clock.advance(TimeDelta::days(1));
+        let message = habit.log_amount(1500.into(), Annotation::default(), &clock).unwrap();
+        assert!(message.contains("day with 1500ml, week with 2500ml"), "{}", message);
+        // Monday starts a new week
+        clock.advance(TimeDelta::days(6));
+        habit.log_amount(200.into(), Annotation::default(), &clock).unwrap();
+        let records = habit.records();
+        assert_eq!(records.day, Some(Best { date: date("2024-02-27"), value: 1500.0 }));
+        assert_eq!(records.week, Some(Best { date: date("2024-02-26"), value: 2500.0 }));
+    }
+
+    #[test]
+    fn archiving_keeps_history_until_restored(){
+        let clock = FixedClock::at("2024-01-01 08:00");
+        let mut habit = Habit::new_streak("read".to_string(), &clock);
+        complete_daily(&mut habit, &clock, 3);
+        habit.archive(&clock).unwrap();
+        assert!(habit.archive(&clock).is_err());
+        assert_eq!(habit.archived(), Some(date("2024-01-04")));
+        let Habit::Streak(s) = &habit else { unreachable!() };
+        assert_eq!(s.completions.len(), 3);
+        habit.restore(&clock).unwrap();
+        assert!(!habit.is_archived());
+        assert!(habit.restore(&clock).is_err());
+    }
+
+    #[test]
+    fn changing_unit_size_leaves_past_days_alone(){
+        let clock = FixedClock::at("2024-01-01 08:00");
+        let mut habit = Habit::new_quantity("water".to_string(), "ml".to_string(), 250.0, &clock);
+        habit.log_amount(2.into(), Annotation::default(), &clock).unwrap();
+        habit.edit("unit_size", "330").unwrap();
+        habit.log_amount(1.into(), Annotation::default(), &clock).unwrap();
+        clock.advance(TimeDelta::days(1));
+        habit.log_amount(1.into(), Annotation::default(), &clock).unwrap();
+        let Habit::Quantity(q) = &habit else { unreachable!() };
+        assert_eq!(q.log.history[0].value, 830.0);
+        assert_eq!(q.log.today_total, 330.0);
+        assert!(habit.edit("unit_size", "0").is_err());
+    }
+
+    #[test]
+    fn amounts_with_units_are_stored_in_the_habit_unit(){
+        let clock = FixedClock::at("2024-01-01 08:00");
+        let mut habit = Habit::new_quantity("water".to_string(), "ml".to_string(), 250.0, &clock);
+        habit.log_amount(Amount::parse("0.5l").unwrap(), Annotation::default(), &clock).unwrap();
+        habit.log_amount(Amount::parse("12oz").unwrap(), Annotation::default(), &clock).unwrap();
+        assert!(habit.log_amount(Amount::parse("2km").unwrap(), Annotation::default(), &clock).is_err());
+        habit.edit("display", "l").unwrap();
+        assert!(habit.edit("display", "kg").is_err());
+        let Habit::Quantity(q) = &habit else { unreachable!() };
+        assert_eq!(q.log.today_total, 854.882);
+        assert_eq!(q.show(q.log.today_total), "0.85l");
+    }
+
+    #[test]
+    fn whole_number_amounts_load_and_take_decimals(){
+        let clock = FixedClock::at("2024-01-02 08:00");
+        let json = r#"{"Quantity": {"name": "run", "unit": "km", "unit_size": 1, "today_total": 3,
+            "today_date": "2024-01-02", "today_history": [{"hour": 7, "value": 3}],
+            "history": [{"date": "2024-01-01", "value": 5, "met": true}], "target": {"AtLeast": 4}, "streak": 1}}"#;
+        let mut habit: Habit = serde_json::from_str(json).unwrap();
+        habit.log_amount(Amount::parse("1.2").unwrap(), Annotation::default(), &clock).unwrap();
+        habit.log_amount(Amount::parse("0.1").unwrap(), Annotation::default(), &clock).unwrap();
+        let Habit::Quantity(q) = &habit else { unreachable!() };
+        assert_eq!(q.log.history[0].value, 5.0);
+        assert_eq!(q.log.today_total, 4.3);
+        assert_eq!(q.streak, 2);
+    }
+
+    #[test]
+    fn corrections_recompute_the_day_from_its_entries(){
+        let clock = FixedClock::at("2024-01-01 08:00");
+        let mut habit = Habit::new_quantity("water".to_string(), "ml".to_string(), 500.0, &clock);
+        habit.set_target(Some(Target::AtLeast(2000.0))).unwrap();
+        for amount in ["1", "10", "250ml"] {
+            habit.log_amount(Amount::parse(amount).unwrap(), Annotation::default(), &clock).unwrap();
+        }
+        assert_eq!(habit.streak(clock.today()), Some(1));
+        habit.edit_entry(2, Amount::parse("1").unwrap(), &clock).unwrap();
+        habit.delete_entry(3, &clock).unwrap();
+        assert!(habit.delete_entry(3, &clock).is_err());
+        habit.log_amount(Amount::parse("-250ml").unwrap(), Annotation::default(), &clock).unwrap();
+        assert!(habit.log_amount(Amount::parse("-1l").unwrap(), Annotation::default(), &clock).is_err());
+        assert_eq!(habit.entries(&clock).unwrap().len(), 3);
+        let Habit::Quantity(q) = &habit else { unreachable!() };
+        assert_eq!(q.log.today_total, 750.0);
+        assert_eq!(q.streak, 0);
+        assert_eq!(habit.records().day.map(|b| b.value), Some(750.0));
+    }
+
+    #[test]
+    fn entries_keep_minutes_and_hour_only_ones_are_migrated(){
+        let json = r#"{"today_total": 3, "today_date": "2024-01-02", "history": [],
+            "today_history": [{"hour": 11, "value": 2, "at": "2024-01-02T11:40:00"}, {"hour": 7, "value": 1}]}"#;
+        let mut log: DayLog = serde_json::from_str(json).unwrap();
+        let clock = FixedClock::at("2024-01-02 11:05");
+        log.record(4.0, Annotation::default(), &clock, |_| None);
+        clock.advance(TimeDelta::minutes(50));
+        log.record(0.5, Annotation::default(), &clock, |_| None);
+        let times: Vec<String> = log.today_history.iter()
+            .map(|e| clock.home().home_time(e.at).format("%H:%M").to_string())
+            .collect();
+        assert_eq!(times, ["07:00", "11:05", "11:40", "11:55"]);
+        assert_eq!(log.by_hour(clock.home()), BTreeMap::from([(7, 1.0), (11, 6.5)]));
+        assert_eq!(log.today_total, 7.5);
+    }
+
+    #[test]
+    fn loaded_total_comes_from_the_entries(){
+        let json = r#"{"today_total": 0, "today_date": "2024-01-02", "history": [],
+            "today_history": [{"hour": 8, "value": 1000}, {"hour": 9, "value": 1500}]}"#;
+        let mut log: DayLog = serde_json::from_str(json).unwrap();
+        assert_eq!(log.today_total, 2500.0);
+        let clock = FixedClock::at("2024-01-02 10:00");
+        log.record(500.0, Annotation::default(), &clock, |_| None);
+        assert_eq!(log.today_total, 3000.0);
+    }
+
+    #[test]
+    fn late_logs_count_for_the_day_before_until_the_day_starts(){
+        let home = Home { day_starts_at: 4, timezone: None };
+        let clock = FixedClock::at_home("2024-01-02 00:30", home);
+        let mut habit = Habit::new_quantity("water".to_string(), "ml".to_string(), 1.0, &clock);
+        habit.log_amount(500.into(), Annotation::default(), &clock).unwrap();
+        assert_eq!(clock.today(), date("2024-01-01"));
+        clock.advance(TimeDelta::hours(4));
+        habit.log_amount(250.into(), Annotation::default(), &clock).unwrap();
+        let Habit::Quantity(q) = &habit else { unreachable!() };
+        assert_eq!(q.log.today_date, date("2024-01-02"));
+        assert_eq!((q.log.history[0].date, q.log.history[0].value), (date("2024-01-01"), 500.0));
+    }
+
+    #[test]
+    fn naive_times_load_as_home_time(){
+        let home = Home { day_starts_at: 4, timezone: Some(chrono_tz::Pacific::Auckland) };
+        let json = r#"{"Abstinence": {"name": "smoking", "started": "2024-01-01", "streak": 0,
+            "relapses": [{"at": "2024-01-11T02:00:00", "note": "party"}]}}"#;
+        let habit: Habit = timestamp::reading(home, || serde_json::from_str(json)).unwrap();
+        let Habit::Abstinence(a) = &habit else { unreachable!() };
+        let relapse = &a.relapses[0];
+        assert_eq!(relapse.at.to_rfc3339(), "2024-01-11T02:00:00+13:00");
+        assert_eq!(relapse.day, date("2024-01-10"));
+        assert_eq!(a.days_clean(date("2024-01-12")), 2);
+    }
+
+    #[test]
+    fn changing_unit_converts_past_amounts(){
+        let clock = FixedClock::at("2024-01-01 08:00");
+        let mut habit = Habit::new_quantity("water".to_string(), "ml".to_string(), 250.0, &clock);
+        habit.set_target(Some(Target::AtLeast(2000.0))).unwrap();
+        habit.log_amount(Amount::parse("3.5l").unwrap(), Annotation::default(), &clock).unwrap();
+        clock.advance(TimeDelta::days(1));
+        habit.log_amount(2.into(), Annotation::default(), &clock).unwrap();
+        habit.edit("unit", "l").unwrap();
+        assert!(habit.edit("unit", "km").is_err());
+        let Habit::Quantity(q) = &habit else { unreachable!() };
+        assert_eq!((q.log.history[0].value, q.log.today_total), (3.5, 0.5));
+        assert_eq!((q.unit_size, q.target), (0.25, Some(Target::AtLeast(2.0))));
+        assert_eq!(habit.records().day.map(|b| b.value), Some(3.5));
+        assert_eq!(habit.streak(clock.today()), Some(1));
+        habit.relabel_unit("glasses").unwrap();
+        assert_eq!(habit.unit(), Some("glasses"));
+    }
+
+    #[test]
+    fn renaming_checks_the_new_name(){
+        let clock = FixedClock::at("2024-01-01 08:00");
+        let mut habit = Habit::new_streak("read".to_string(), &clock);
+        assert!(habit.rename("Read Books").is_err());
+        assert!(habit.rename("read").is_err());
+        habit.rename("read-books").unwrap();
+        assert_eq!(habit.name(), "read-books");
+    }
+
+    #[test]
+    fn loading_gives_every_habit_its_own_id(){
+        let clock = FixedClock::at("2024-01-01 08:00");
+        let mut old = StreakHabit::new("read".to_string());
+        old.completions.insert(date("2023-12-25"));
+        let copy = Habit::new_streak("run".to_string(), &clock);
+        let mut habits = vec![Habit::Streak(old), copy.clone(), copy];
+        assign_ids(&mut habits, &clock);
+        let ids: BTreeSet<&str> = habits.iter().map(|h| h.id()).collect();
+        assert_eq!(ids.len(), 3);
+        assert!(ids.iter().all(|id| id.len() == 8));
+        let created = habits[0].identity().created.unwrap();
+        assert_eq!(clock.home().day_of(created), date("2023-12-25"));
+    }
+
+    #[test]
+    fn reset_only_takes_back_today(){
+        let clock = FixedClock::at("2026-01-03 09:00");
+        let mut read = Habit::new_streak("read".to_string(), &clock);
+        let mut chores = Habit::new_checklist("chores".to_string(), vec!["dishes".to_string()], &clock);
+        let mut smoking = Habit::new_abstinence("smoking".to_string(), date("2026-01-01"), &clock);
+        smoking.relapse(None, &clock).unwrap();
+        clock.advance(TimeDelta::days(2));
+        for day in ["2026-01-03", "2026-01-04", "2026-01-05"] {
+            read.complete_on(date(day), Annotation::default(), &clock).unwrap();
+        }
+        chores.check_item("dishes", Some(date("2026-01-04")), true, Annotation::default(), &clock).unwrap();
+        chores.check_item("dishes", None, true, Annotation::default(), &clock).unwrap();
+        smoking.relapse(None, &clock).unwrap();
+        for habit in [&mut read, &mut chores, &mut smoking] {
+            habit.reset(&clock).unwrap();
+        }
+        let Habit::Streak(s) = &read else { unreachable!() };
+        assert_eq!(s.completions.iter().copied().collect::<Vec<_>>(), [date("2026-01-03"), date("2026-01-04")]);
+        assert_eq!(read.streak(clock.today()), Some(2));
+        let Habit::Checklist(c) = &chores else { unreachable!() };
+        assert_eq!(c.completed_days().into_iter().collect::<Vec<_>>(), [date("2026-01-04")]);
+        let Habit::Abstinence(a) = &smoking else { unreachable!() };
+        assert_eq!(a.relapses.iter().map(|r| r.day).collect::<Vec<_>>(), [date("2026-01-03")]);
+        assert_eq!(smoking.streak(clock.today()), Some(2));
+    }
+
+    #[test]
+    fn backfilling_a_missed_day_joins_the_runs_around_it(){
+        let today = date("2026-01-05");
+        let mut habit = StreakHabit::new("read".to_string());
+        for day in ["2026-01-01", "2026-01-02", "2026-01-04", "2026-01-05"] {
+            habit.complete_on(date(day), today).unwrap();
+        }
+        assert_eq!((habit.streak, habit.longest_streak(today)), (2, 2));
+        habit.complete_on(date("2026-01-03"), today).unwrap();
+        assert_eq!((habit.streak, habit.longest_streak(today)), (5, 5));
+    }
+
+    #[test]
+    fn backfilling_refuses_future_and_repeated_days(){
+        let today = date("2026-01-05");
+        let mut habit = StreakHabit::new("read".to_string());
+        assert!(habit.complete_on(date("2026-01-06"), today).is_err());
+        habit.complete_on(date("2026-01-04"), today).unwrap();
+        assert!(habit.complete_on(date("2026-01-04"), today).is_err());
+        assert_eq!(habit.completions.len(), 1);
+    }
+
+    #[test]
+    fn freezes_are_earned_by_successes_and_spent_on_missed_days(){
+        let clock = FixedClock::at("2026-01-10 09:00");
+        let mut habit = Habit::new_streak("read".to_string(), &clock);
+        for day in ["2026-01-01", "2026-01-02", "2026-01-03", "2026-01-04", "2026-01-05", "2026-01-06"] {
+            habit.complete_on(date(day), Annotation::default(), &clock).unwrap();
+        }
+        assert_eq!(habit.freezes_available(clock.today()), 0);
+        habit.complete_on(date("2026-01-07"), Annotation::default(), &clock).unwrap();
+        habit.complete_on(date("2026-01-09"), Annotation::default(), &clock).unwrap();
+        assert_eq!(habit.freezes_available(clock.today()), 1);
+        assert!(habit.excuse(date("2026-01-09"), Excuse::Freeze, &clock).is_err());
+        assert_eq!(habit.streak(clock.today()), Some(1));
+        habit.excuse(date("2026-01-08"), Excuse::Freeze, &clock).unwrap();
+        assert_eq!(habit.streak(clock.today()), Some(8));
+        assert_eq!(habit.freezes_available(clock.today()), 0);
+        assert!(habit.excuse(date("2026-01-10"), Excuse::Freeze, &clock).is_err());
+    }
+
+    #[test]
+    fn resetting_a_timer_drops_only_today_and_waits_for_it_to_stop(){
+        let clock = FixedClock::at("2026-01-03 09:00");
+        let mut habit = Habit::new_timed("piano".to_string(), &clock);
+        habit.start_timer(&clock).unwrap();
+        clock.advance(TimeDelta::minutes(30));
+        habit.stop_timer(Annotation::default(), &clock).unwrap();
+        clock.advance(TimeDelta::days(1));
+        habit.start_timer(&clock).unwrap();
+        clock.advance(TimeDelta::minutes(20));
+        assert!(habit.reset(&clock).is_err());
+        habit.stop_timer(Annotation::default(), &clock).unwrap();
+        habit.reset(&clock).unwrap();
+        let Habit::Timed(t) = &habit else { unreachable!() };
+        assert_eq!(t.sessions.iter().map(|s| s.minutes()).collect::<Vec<_>>(), [30]);
+        assert_eq!((t.log.today_total, t.log.history.iter().map(|e| e.value).collect::<Vec<_>>()), (0.0, vec![30.0]));
+    }
+
+    #[test]
+    fn tags_are_checked_and_only_changed_when_new(){
+        let clock = FixedClock::at("2026-01-03 09:00");
+        let mut habit = Habit::new_streak("read".to_string(), &clock);
+        assert!(habit.tag(&["Deep Work"]).is_err());
+        assert!(habit.tags().is_empty());
+        habit.tag(&["health", "work"]).unwrap();
+        assert!(habit.tag(&["work"]).is_err());
+        assert_eq!(habit.tag(&["work", "home"]).unwrap(), "Tagged read with home");
+        assert!(habit.untag(&["hobby"]).is_err());
+        habit.untag(&["home", "hobby"]).unwrap();
+        assert_eq!(habit.tags().iter().collect::<Vec<_>>(), ["health", "work"]);
+    }
+
+    #[test]
+    fn list_and_stats_filter_by_tag_and_archive(){
+        let clock = FixedClock::at("2026-01-03 09:00");
+        let mut habit = Habit::new_streak("read".to_string(), &clock);
+        habit.tag(&["work"]).unwrap();
+        assert!(habit.is_shown(None, false) && habit.is_shown(Some("work"), false));
+        assert!(!habit.is_shown(Some("health"), false) && !habit.is_shown(None, true));
+        habit.archive(&clock).unwrap();
+        assert!(habit.is_shown(Some("work"), true) && !habit.is_shown(Some("work"), false));
+    }
+
+    #[test]
+    fn ratings_must_be_one_to_five(){
+        assert!(Annotation::new(None, Some(0)).is_err());
+        assert!(Annotation::new(None, Some(6)).is_err());
+        let annotation = Annotation::new(Some("easy".to_string()), Some(5)).unwrap();
+        assert_eq!(annotation.to_string(), "easy (5/5)");
+        assert!(Annotation::new(None, None).unwrap().is_empty());
+    }
+
+    #[test]
+    fn notes_carry_over_into_history_and_turn_up_in_search(){
+        let clock = FixedClock::at("2026-01-03 09:00");
+        let mut habit = Habit::new_quantity("water".to_string(), "ml".to_string(), 1.0, &clock);
+        let note = Annotation::new(Some("After the Run".to_string()), Some(4)).unwrap();
+        habit.log_amount(500.into(), note.clone(), &clock).unwrap();
+        habit.log_amount(250.into(), Annotation::default(), &clock).unwrap();
+        clock.advance(TimeDelta::days(1));
+        habit.roll_over(clock.today());
+        let Habit::Quantity(q) = &habit else { unreachable!() };
+        assert_eq!(q.log.history[0].notes, vec![note.clone()]);
+        assert_eq!(habit.annotations(clock.home()), [(date("2026-01-03"), note)]);
+        let found: Vec<_> = habit.annotations(clock.home()).into_iter().filter(|(_, a)| a.matches("the run")).collect();
+        assert_eq!(found.len(), 1);
+        assert!(!habit.annotations(clock.home())[0].1.matches("swim"));
+    }
+
+    #[test]
+    fn completion_rules_parse(){
+        assert_eq!(CompletionRule::parse("all"), Ok(CompletionRule::All));
+        assert_eq!(CompletionRule::parse("any"), Ok(CompletionRule::AtLeast(1)));
+        assert_eq!(CompletionRule::parse("2"), Ok(CompletionRule::AtLeast(2)));
+        assert!(CompletionRule::parse("0").is_err());
+        assert!(CompletionRule::parse("most").is_err());
+    }
+
+    #[test]
+    fn checklist_needs_its_rule_met_and_unchecking_breaks_the_day(){
+        let today = date("2026-01-03");
+        let items = ["stretch", "shower", "journal"].map(str::to_string).to_vec();
+        let mut routine = ChecklistHabit::new("morning".to_string(), items);
+        routine.check("stretch", date("2026-01-02"), today).unwrap();
+        routine.check("shower", date("2026-01-02"), today).unwrap();
+        routine.check_all(today, today);
+        assert!(!routine.is_complete_on(date("2026-01-02")));
+        assert_eq!(routine.streak, 1);
+        routine.set_rule(CompletionRule::AtLeast(2), today);
+        assert!(routine.is_complete_on(date("2026-01-02")));
+        assert_eq!(routine.streak, 2);
+        // More than there are items means all of them
+        routine.set_rule(CompletionRule::AtLeast(5), today);
+        assert_eq!(routine.required(), 3);
+        assert_eq!(routine.streak, 1);
+        routine.uncheck("journal", today, today).unwrap();
+        assert_eq!((routine.checked_on(today), routine.streak), (2, 0));
+        assert!(routine.check("meditate", today, today).is_err());
+    }
+}
@@ -1,19 +1,48 @@
 mod habit;
 mod storage;
+mod schedule;
 mod validation;
 mod cli;
+mod config;
 mod tui;
+mod undo;
+mod timestamp;
+mod clock;
+mod units;
 pub mod stats;
 
 use std::env;
 
+use chrono::NaiveDate;
+
+use crate::clock::{Clock, SimulatedClock, SystemClock};
+
 fn main() -> Result<(), Box<dyn std::error::Error>> {
-    let args: Vec<String> = env::args().collect();
-    
-    if args.len() > 1 && args[1] == "--tui" {
-        tui::run()?;
+    let mut args = env::args().skip(1);
+    let mut use_tui = false;
+    // --today <date> pretends it is another day, to try out rollovers
+    let mut today: Option<NaiveDate> = None;
+    while let Some(arg) = args.next() {
+        match arg.as_str() {
+            "--tui" => use_tui = true,
+            "--today" => {
+                let date = args.next().ok_or("--today needs a date like 2024-02-29")?;
+                today = Some(NaiveDate::parse_from_str(&date, "%Y-%m-%d")
+                    .map_err(|_| format!("'{}' is not a date like 2024-02-29", date))?);
+            }
+            other => return Err(format!("Unknown option '{}'. Try --tui or --today <date>", other).into()),
+        }
+    }
+    let home = storage::load_config().unwrap_or_default().home();
+    let clock: Box<dyn Clock> = match today {
+        Some(today) => Box::new(SimulatedClock::new(today, home)),
+        None => Box::new(SystemClock::new(home)),
+    };
+
+    if use_tui {
+        tui::run(clock.as_ref())?;
     } else {
-        cli::run();
+        cli::run(clock.as_ref());
     }
     
     Ok(())
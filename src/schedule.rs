@@ -0,0 +1,291 @@
+use std::cmp::Ordering;
+use std::collections::{BTreeMap, BTreeSet};
+use std::fmt;
+
+use chrono::{Datelike, Days, NaiveDate, Weekday};
+use serde::{Deserialize, Serialize};
+
+// When a habit is expected to be done
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub enum Schedule{
+    #[default]
+    Daily,
+    // Only these days count, e.g. gym on Mon/Wed/Fri
+    Weekdays(Vec<Weekday>),
+    // At least this many completions in each Monday-Sunday week
+    TimesPerWeek(u32),
+    // No more than this many days between completions
+    EveryNDays(u32),
+}
+
+// A stretch of completions that kept the habit on schedule
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Run{
+    pub start: NaiveDate,
+    pub end: NaiveDate,
+    pub count: u32,
+}
+
+// Where the latest run stands as of today
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum StreakStatus{
+    // Done for now, or nothing is due yet
+    Current,
+    // Still alive, but gone if nothing is done in the period due now
+    AtRisk,
+    // A period went by without being done
+    Broken,
+}
+
+impl Schedule{
+    // Parses the same text `Display` writes: daily, mon,wed,fri, 3/week, every-2-days
+    pub fn parse(spec: &str) -> Result<Schedule, String>{
+        let spec = spec.trim().to_lowercase();
+        if spec == "daily" {
+            return Ok(Schedule::Daily);
+        }
+        if let Some(times) = spec.strip_suffix("/week") {
+            return match times.parse::<u32>() {
+                Ok(n) if (1..=7).contains(&n) => Ok(Schedule::TimesPerWeek(n)),
+                _ => Err(format!("'{}' needs 1 to 7 times a week", spec)),
+            };
+        }
+        if let Some(days) = spec.strip_prefix("every-").and_then(|s| s.strip_suffix("-days")) {
+            return match days.parse::<u32>() {
+                Ok(n) if n >= 1 => Ok(Schedule::EveryNDays(n)),
+                _ => Err(format!("'{}' needs a number of days of at least 1", spec)),
+            };
+        }
+        let mut weekdays: Vec<Weekday> = Vec::new();
+        for day in spec.split(',') {
+            let weekday = day.parse::<Weekday>()
+                .map_err(|_| format!("Unknown schedule '{}'. Try daily, mon,wed,fri, 3/week or every-2-days", spec))?;
+            if !weekdays.contains(&weekday) {
+                weekdays.push(weekday);
+            }
+        }
+        weekdays.sort_by_key(|d| d.num_days_from_monday());
+        Ok(Schedule::Weekdays(weekdays))
+    }
+
+    pub fn is_daily(&self) -> bool{
+        *self == Schedule::Daily
+    }
+
+    // Every stretch of on-schedule completions, oldest first. Days the
+    // schedule doesn't ask for neither break nor extend a run, excused days
+    // are treated the same way, and the period containing `today` can't
+    // break one since it isn't over yet.
+    pub fn runs(&self, done: &BTreeSet<NaiveDate>, excused: &BTreeSet<NaiveDate>, today: NaiveDate) -> Vec<Run>{
+        if let Schedule::EveryNDays(n) = self {
+            return interval_runs(done, excused, *n);
+        }
+        let mut periods: BTreeMap<NaiveDate, Vec<NaiveDate>> = BTreeMap::new();
+        for &day in done {
+            if let Some(period) = self.period_of(day) {
+                periods.entry(period).or_default().push(day);
+            }
+        }
+        let current_period = self.period_of(today);
+        let mut runs = Vec::new();
+        let mut current: Option<(Run, NaiveDate)> = None;
+        for (period, days) in periods {
+            let met = days.len() as u32 >= self.required_in(period, excused);
+            if !met && Some(period) != current_period {
+                runs.extend(current.take().map(|(run, _)| run));
+                continue;
+            }
+            let first = days[0];
+            let last = days[days.len() - 1];
+            let count = days.len() as u32;
+            current = match current.take() {
+                Some((mut run, last_period)) if self.follows(last_period, period, excused) => {
+                    run.end = last;
+                    run.count += count;
+                    Some((run, period))
+                }
+                previous => {
+                    runs.extend(previous.map(|(run, _)| run));
+                    Some((Run { start: first, end: last, count }, period))
+                }
+            };
+        }
+        runs.extend(current.map(|(run, _)| run));
+        runs
+    }
+
+    // Length of the latest run and whether it is still going. A broken
+    // run counts as 0
+    pub fn streak(&self, done: &BTreeSet<NaiveDate>, excused: &BTreeSet<NaiveDate>, today: NaiveDate) -> (u32, StreakStatus){
+        let Some(run) = self.runs(done, excused, today).pop() else {
+            return (0, StreakStatus::Current);
+        };
+        match self.status(&run, done, excused, today) {
+            StreakStatus::Broken => (0, StreakStatus::Broken),
+            status => (run.count, status),
+        }
+    }
+
+    fn status(&self, run: &Run, done: &BTreeSet<NaiveDate>, excused: &BTreeSet<NaiveDate>, today: NaiveDate) -> StreakStatus{
+        if let Schedule::EveryNDays(n) = self {
+            let gap = (today - run.end).num_days() - excused.range(run.end..today).count() as i64;
+            return match gap.cmp(&(*n as i64)) {
+                Ordering::Less => StreakStatus::Current,
+                Ordering::Equal => StreakStatus::AtRisk,
+                Ordering::Greater => StreakStatus::Broken,
+            };
+        }
+        // The period today falls in, or the next one the schedule asks for
+        let due = self.period_of(today).or_else(|| self.next_period(today));
+        let (Some(last), Some(due)) = (self.period_of(run.end), due) else {
+            return StreakStatus::Broken;
+        };
+        if last == due {
+            let done_in_due = done.iter().filter(|d| self.period_of(**d) == Some(due)).count() as u32;
+            return if done_in_due >= self.required_in(due, excused) { StreakStatus::Current } else { StreakStatus::AtRisk };
+        }
+        if !self.follows(last, due, excused) {
+            return StreakStatus::Broken;
+        }
+        if self.period_of(today) == Some(due) && self.required_in(due, excused) > 0 {
+            StreakStatus::AtRisk
+        } else {
+            StreakStatus::Current
+        }
+    }
+
+    // The period a completion on `day` counts towards, if any
+    fn period_of(&self, day: NaiveDate) -> Option<NaiveDate>{
+        match self {
+            Schedule::Daily | Schedule::EveryNDays(_) => Some(day),
+            Schedule::Weekdays(days) => days.contains(&day.weekday()).then_some(day),
+            Schedule::TimesPerWeek(_) => Some(day.week(Weekday::Mon).first_day()),
+        }
+    }
+
+    fn next_period(&self, period: NaiveDate) -> Option<NaiveDate>{
+        match self {
+            Schedule::Daily | Schedule::EveryNDays(_) => period.succ_opt(),
+            Schedule::Weekdays(_) => period.iter_days().skip(1).take(7)
+                .find(|d| self.period_of(*d).is_some()),
+            Schedule::TimesPerWeek(_) => period.checked_add_days(Days::new(7)),
+        }
+    }
+
+    // Excused days in a week lower what it takes, so a fully excused
+    // week asks for nothing
+    fn required_in(&self, period: NaiveDate, excused: &BTreeSet<NaiveDate>) -> u32{
+        match self {
+            Schedule::TimesPerWeek(n) => {
+                let excused_days = period.iter_days().take(7).filter(|d| excused.contains(d)).count() as u32;
+                (*n).min(7 - excused_days)
+            }
+            _ => if excused.contains(&period) { 0 } else { 1 },
+        }
+    }
+
+    // Whether `to` comes straight after `from` once excused periods are skipped
+    fn follows(&self, from: NaiveDate, to: NaiveDate, excused: &BTreeSet<NaiveDate>) -> bool{
+        let mut period = self.next_period(from);
+        while let Some(p) = period {
+            if p >= to {
+                return p == to;
+            }
+            if self.required_in(p, excused) > 0 {
+                return false;
+            }
+            period = self.next_period(p);
+        }
+        false
+    }
+}
+
+fn interval_runs(done: &BTreeSet<NaiveDate>, excused: &BTreeSet<NaiveDate>, max_gap: u32) -> Vec<Run>{
+    let mut runs: Vec<Run> = Vec::new();
+    for &day in done {
+        match runs.last_mut() {
+            Some(run) if (day - run.end).num_days() - excused.range(run.end..day).count() as i64 <= max_gap as i64 => {
+                run.end = day;
+                run.count += 1;
+            }
+            _ => runs.push(Run { start: day, end: day, count: 1 }),
+        }
+    }
+    runs
+}
+
+impl fmt::Display for Schedule{
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result{
+        match self {
+            Schedule::Daily => write!(f, "daily"),
+            Schedule::Weekdays(days) => {
+                let names: Vec<String> = days.iter().map(|d| d.to_string().to_lowercase()).collect();
+                write!(f, "{}", names.join(","))
+            }
+            Schedule::TimesPerWeek(n) => write!(f, "{}/week", n),
+            Schedule::EveryNDays(n) => write!(f, "every-{}-days", n),
+        }
+    }
+}
+
+impl fmt::Display for StreakStatus{
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result{
+        match self {
+            StreakStatus::Current => write!(f, "current"),
+            StreakStatus::AtRisk => write!(f, "at risk"),
+            StreakStatus::Broken => write!(f, "broken"),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn days(list: &[&str]) -> BTreeSet<NaiveDate>{
+        list.iter().map(|d| d.parse().unwrap()).collect()
+    }
+
+    fn streak(schedule: Schedule, done: &[&str], today: &str) -> (u32, StreakStatus){
+        schedule.streak(&days(done), &BTreeSet::new(), today.parse().unwrap())
+    }
+
+    #[test]
+    fn daily_streak_is_at_risk_until_done_then_broken(){
+        let done = ["2024-03-04", "2024-03-05"];
+        assert_eq!(streak(Schedule::Daily, &done, "2024-03-05"), (2, StreakStatus::Current));
+        assert_eq!(streak(Schedule::Daily, &done, "2024-03-06"), (2, StreakStatus::AtRisk));
+        assert_eq!(streak(Schedule::Daily, &done, "2024-03-12"), (0, StreakStatus::Broken));
+    }
+
+    #[test]
+    fn excused_day_keeps_the_streak_current(){
+        let excused = days(&["2024-03-06"]);
+        let status = Schedule::Daily.streak(&days(&["2024-03-05"]), &excused, "2024-03-06".parse().unwrap());
+        assert_eq!(status, (1, StreakStatus::Current));
+    }
+
+    #[test]
+    fn weekday_streak_waits_for_the_next_scheduled_day(){
+        let schedule = || Schedule::parse("mon,wed,fri").unwrap();
+        // Monday done, Tuesday isn't asked for
+        assert_eq!(streak(schedule(), &["2024-03-04"], "2024-03-05"), (1, StreakStatus::Current));
+        assert_eq!(streak(schedule(), &["2024-03-04"], "2024-03-06"), (1, StreakStatus::AtRisk));
+        assert_eq!(streak(schedule(), &["2024-03-04"], "2024-03-07"), (0, StreakStatus::Broken));
+    }
+
+    #[test]
+    fn weekly_streak_is_at_risk_until_the_week_is_met(){
+        let done = ["2024-02-26", "2024-02-28", "2024-03-04"];
+        assert_eq!(streak(Schedule::TimesPerWeek(2), &done, "2024-03-05"), (3, StreakStatus::AtRisk));
+        assert_eq!(streak(Schedule::TimesPerWeek(2), &done, "2024-03-11"), (0, StreakStatus::Broken));
+    }
+
+    #[test]
+    fn interval_streak_is_at_risk_on_its_last_day(){
+        let done = ["2024-03-01"];
+        assert_eq!(streak(Schedule::EveryNDays(3), &done, "2024-03-03"), (1, StreakStatus::Current));
+        assert_eq!(streak(Schedule::EveryNDays(3), &done, "2024-03-04"), (1, StreakStatus::AtRisk));
+        assert_eq!(streak(Schedule::EveryNDays(3), &done, "2024-03-05"), (0, StreakStatus::Broken));
+    }
+}
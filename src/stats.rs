@@ -1,5 +1,11 @@
 
-use crate::habit::{Habit, StreakHabit};
+use std::collections::BTreeMap;
+
+use chrono::NaiveDate;
+use itertools::Itertools;
+
+use crate::config::Home;
+use crate::habit::{AbstinenceHabit, Habit, HabitFunctions, QuantityHabit};
 
 pub struct StreakStats {
     pub total: usize,
@@ -10,19 +16,23 @@ pub struct StreakStats {
 }
 
 impl StreakStats {
-    pub fn calculate(habits: &[StreakHabit]) -> Self {
-        // TODO: Your implementation!
-        // Calculate all the stats
-        // Return StreakStats { ... }
-        let total = habits.len();
-        let active = habits.iter()
-                            .filter(|h|h.streak>0).count();
-        let longest = habits.iter()
-                            .map(|h| h.streak)
+    pub fn calculate(habits: &[Habit], today: NaiveDate) -> Self {
+        // Every habit that keeps a streak: all but timed ones and quantity
+        // habits without a target
+        let habits: Vec<&Habit> = habits.iter().filter(|h| !h.is_archived()).collect();
+        let streaks: Vec<u32> = habits.iter()
+                            .filter_map(|h| h.streak(today))
+                            .collect();
+        let total = streaks.len();
+        let active = streaks.iter()
+                            .filter(|s| **s>0).count();
+        // Records remember runs that have since broken
+        let longest = streaks.iter()
+                            .copied()
+                            .chain(habits.iter().filter_map(|h| h.records().streak.map(|r| r.count)))
                             .max()
                             .unwrap_or(0);
-        let total_days:u32 = habits.iter()
-                            .map(|h|h.streak).sum();
+        let total_days:u32 = streaks.iter().sum();
         let average = if total_days>0{total_days as f64 / total as f64}
             else{
                 0 as f64
@@ -40,6 +50,98 @@ impl StreakStats {
     }
 }
 
-// pub struct QuantityStats{
-//     pub 
-// }
\ No newline at end of file
+pub struct LimitStats {
+    pub total: usize,
+    pub within_today: usize,
+    pub longest_clean: u32,
+    pub days_over: usize,
+}
+
+impl LimitStats {
+    pub fn calculate(habits: &[Habit], today: NaiveDate) -> Self {
+        let limits: Vec<_> = habits.iter()
+                            .filter_map(|h| match h {
+                                Habit::Limit(l) if l.filing.archived.is_none() => Some(l),
+                                _ => None,
+                            })
+                            .collect();
+        let total = limits.len();
+        let within_today = limits.iter()
+                            .filter(|l| !l.over_today()).count();
+        let longest_clean = limits.iter()
+                            .map(|l| l.longest_streak(today))
+                            .max()
+                            .unwrap_or(0);
+        let days_over = limits.iter()
+                            .map(|l| l.over_days().len()).sum();
+        LimitStats{ total, within_today, longest_clean, days_over }
+    }
+
+    pub fn display_stats(&self) -> String {
+        format!(
+            "Within limit today {}/{} • Longest clean run {} • Days over {}",
+            self.within_today, self.total, self.longest_clean, self.days_over
+        )
+    }
+}
+
+pub struct AbstinenceStats {
+    pub days_clean: u32,
+    pub longest_clean: u32,
+    pub relapses: usize,
+    pub per_month: f64,
+}
+
+impl AbstinenceStats {
+    pub fn calculate(habit: &AbstinenceHabit, today: NaiveDate) -> Self {
+        AbstinenceStats {
+            days_clean: habit.days_clean(today),
+            longest_clean: habit.longest_clean(today),
+            relapses: habit.relapses.len(),
+            per_month: habit.relapses_per_month(today),
+        }
+    }
+
+    pub fn display_stats(&self) -> String {
+        format!(
+            "Clean {} days • Longest {} • Relapses {} ({:.1}/month)",
+            self.days_clean, self.longest_clean, self.relapses, self.per_month
+        )
+    }
+}
+
+pub struct QuantityStats {
+    pub today: f64,
+    pub average: f64,
+    pub best_day: f64,
+    pub days: usize,
+    // Today's amounts per hour of the day
+    pub by_hour: BTreeMap<u32, f64>,
+}
+
+impl QuantityStats {
+    // Past days only, so a half-logged today doesn't drag the average down
+    pub fn calculate(habit: &QuantityHabit, home: Home) -> Self {
+        let days = habit.log.history.len();
+        let total: f64 = habit.log.history.iter().map(|e| e.value).sum();
+        let average = if days > 0 { total / days as f64 } else { 0.0 };
+        let best_day = habit.log.history.iter()
+                            .map(|e| e.value)
+                            .fold(habit.log.today_total, f64::max);
+        QuantityStats { today: habit.log.today_total, average, best_day, days, by_hour: habit.log.by_hour(home) }
+    }
+
+    // Amounts are converted to the habit's display unit
+    pub fn display_stats(&self, habit: &QuantityHabit) -> String {
+        format!(
+            "Today {} • Daily average {} over {} days • Best day {}",
+            habit.show(self.today), habit.show(self.average), self.days, habit.show(self.best_day)
+        )
+    }
+
+    pub fn display_hours(&self, habit: &QuantityHabit) -> String {
+        self.by_hour.iter()
+            .map(|(hour, value)| format!("{:02}h {}", hour, habit.show(*value)))
+            .join(" · ")
+    }
+}
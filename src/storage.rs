@@ -1,15 +1,54 @@
 use std::fs;
 use std::io;
-use crate::habit::Habit;  // ← Import from our module!
-use serde_json;
+use crate::clock::Clock;
+use crate::config::Config;
+use crate::habit::{assign_ids, Habit};  // ← Import from our module!
+use crate::timestamp;
+use crate::undo::UndoStack;
 
 pub fn save_habits(habits: &[Habit]) -> Result<(), io::Error> {
-    let json_payload = serde_json::to_string_pretty(habits).map_err(|e|io::Error::new(io::ErrorKind::Other, e))?;
+    let json_payload = serde_json::to_string_pretty(habits).map_err(io::Error::other)?;
     fs::write("habits.json", json_payload)?;
     Ok(())
 }
 
-pub fn load_habits() -> Result<Vec<Habit>, io::Error> {
+pub fn load_habits(clock: &dyn Clock) -> Result<Vec<Habit>, io::Error> {
     let data = fs::read_to_string("habits.json")?;
-    serde_json::from_str(&data).map_err(|e|io::Error::new(io::ErrorKind::Other, e))
-}
\ No newline at end of file
+    let mut habits: Vec<Habit> = timestamp::reading(clock.home(), || serde_json::from_str(&data))
+        .map_err(io::Error::other)?;
+    let today = clock.today();
+    // Vacations also cover habits added after they were set
+    let config = load_config().unwrap_or_default();
+    for habit in habits.iter_mut() {
+        habit.migrate(today);
+        habit.apply_vacations(&config.vacations, today);
+    }
+    assign_ids(&mut habits, clock);
+    Ok(habits)
+}
+
+pub fn save_undo(stack: &UndoStack) -> Result<(), io::Error> {
+    let json_payload = serde_json::to_string(stack).map_err(io::Error::other)?;
+    fs::write("undo.json", json_payload)?;
+    Ok(())
+}
+
+// `habits` are the ones just loaded, whose IDs older snapshots take on
+pub fn load_undo(habits: &[Habit], clock: &dyn Clock) -> Result<UndoStack, io::Error> {
+    let data = fs::read_to_string("undo.json")?;
+    let mut stack: UndoStack = timestamp::reading(clock.home(), || serde_json::from_str(&data))
+        .map_err(io::Error::other)?;
+    stack.migrate(habits, clock);
+    Ok(stack)
+}
+
+pub fn save_config(config: &Config) -> Result<(), io::Error> {
+    let json_payload = serde_json::to_string_pretty(config).map_err(io::Error::other)?;
+    fs::write("config.json", json_payload)?;
+    Ok(())
+}
+
+pub fn load_config() -> Result<Config, io::Error> {
+    let data = fs::read_to_string("config.json")?;
+    serde_json::from_str(&data).map_err(io::Error::other)
+}
@@ -0,0 +1,53 @@
+use std::cell::Cell;
+
+use chrono::{DateTime, FixedOffset, NaiveDateTime};
+use serde::{Deserialize, Deserializer};
+
+use crate::config::Home;
+
+// A moment in time together with the UTC offset it was recorded at
+pub type Timestamp = DateTime<FixedOffset>;
+
+thread_local! {
+    // Home of the file being read, set only for the length of `reading`
+    static READING_HOME: Cell<Home> = Cell::new(Home::default());
+}
+
+// Runs `read` with naive times and hour-only entries taken as `home` time.
+// Serde has no way to hand a value to a field, so it is lent for the call
+pub fn reading<T>(home: Home, read: impl FnOnce() -> T) -> T{
+    let before = READING_HOME.replace(home);
+    let result = read();
+    READING_HOME.set(before);
+    result
+}
+
+// Home of the file being read
+pub fn reading_home() -> Home{
+    READING_HOME.get()
+}
+
+// Files written before timestamps had offsets store home wall clock times
+#[derive(Deserialize)]
+#[serde(untagged)]
+enum Stored{
+    WithOffset(Timestamp),
+    Naive(NaiveDateTime),
+}
+
+impl From<Stored> for Timestamp{
+    fn from(stored: Stored) -> Self{
+        match stored {
+            Stored::WithOffset(at) => at,
+            Stored::Naive(naive) => reading_home().at_home_time(naive),
+        }
+    }
+}
+
+pub fn deserialize<'de, D: Deserializer<'de>>(deserializer: D) -> Result<Timestamp, D::Error>{
+    Stored::deserialize(deserializer).map(Timestamp::from)
+}
+
+pub fn deserialize_option<'de, D: Deserializer<'de>>(deserializer: D) -> Result<Option<Timestamp>, D::Error>{
+    Option::<Stored>::deserialize(deserializer).map(|s| s.map(Timestamp::from))
+}
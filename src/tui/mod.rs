@@ -1,17 +1,118 @@
-use std::{fmt::format, io, os::macos::raw::stat};
+use std::{collections::BTreeMap, io, time::Duration};
+
+use chrono::NaiveDate;
+use itertools::Itertools;
 
 use crossterm::{
-    ExecutableCommand, event::{self, Event, KeyCode}, execute, terminal::{EnterAlternateScreen, LeaveAlternateScreen, disable_raw_mode, enable_raw_mode}
+    ExecutableCommand, event::{self, Event, KeyCode, KeyEventKind, KeyModifiers}, execute, terminal::{EnterAlternateScreen, LeaveAlternateScreen, disable_raw_mode, enable_raw_mode}
 };
 use ratatui::{
-    Terminal, backend::CrosstermBackend, layout::{Constraint, Direction, Layout}, style::{Color, Modifier, Style, Stylize}, widgets::{List, ListItem, Paragraph, Widget}  // Add these
+    Terminal, backend::CrosstermBackend, layout::{Constraint, Direction, Layout}, style::Stylize, widgets::{Block, Borders, List, ListItem, ListState, Paragraph}
 };
 
 
 // use crate::{habit::Habit, stats::HabitStats};
-use crate::{habit::{Habit, HabitFunctions}, storage::load_habits};
+use crate::{clock::Clock, config::Vacation, habit::{Annotation, Habit, HabitFunctions}, stats::StreakStats, storage::{load_config, load_habits, load_undo, save_habits, save_undo}, undo::UndoStack};
+
+struct App<'a>{
+    habits: Vec<Habit>,
+    undo: UndoStack,
+    list_state: ListState,
+    clock: &'a dyn Clock,
+    // The day habits were last rolled over to
+    day: NaiveDate,
+    // From the config, put back on habits after an undo or redo
+    vacations: Vec<Vacation>,
+    // Feedback from the last key press, shown above the commands
+    message: String,
+}
+
+impl App<'_>{
+    // Moves through habits in the order they are shown, sections and all
+    fn select_next(&mut self){
+        self.select_step(true);
+    }
+    fn select_previous(&mut self){
+        self.select_step(false);
+    }
+    fn select_step(&mut self, forward: bool){
+        let order = order(&self.habits);
+        if order.is_empty() {
+            return;
+        }
+        let step = if forward { 1 } else { order.len() - 1 };
+        let position = self.list_state.selected()
+            .and_then(|i| order.iter().position(|h| *h == i))
+            .map_or(0, |p| (p + step) % order.len());
+        self.list_state.select(Some(order[position]));
+    }
+    fn complete_selected(&mut self){
+        let Some(index) = self.list_state.selected() else { return };
+        let before = self.habits.clone();
+        let habit = &mut self.habits[index];
+        self.message = match habit.complete(Annotation::default(), self.clock) {
+            Ok(msg) => {
+                let label = format!("complete {}", habit.name());
+                self.undo.record(&label, before);
+                msg
+            }
+            Err(e) => e,
+        };
+    }
+    // Starts the selected timed habit, or stops it if it is running
+    fn toggle_timer_selected(&mut self){
+        let Some(index) = self.list_state.selected() else { return };
+        let before = self.habits.clone();
+        let habit = &mut self.habits[index];
+        let (verb, result) = match habit {
+            Habit::Timed(t) if t.running.is_some() => ("stop", habit.stop_timer(Annotation::default(), self.clock)),
+            _ => ("start", habit.start_timer(self.clock)),
+        };
+        self.message = match result {
+            Ok(msg) => {
+                let label = format!("{} {}", verb, habit.name());
+                self.undo.record(&label, before);
+                msg
+            }
+            Err(e) => e,
+        };
+    }
+    fn undo(&mut self){
+        self.message = match self.undo.undo(&mut self.habits, self.clock.today(), &self.vacations) {
+            Some(label) => format!("Undid: {}", label),
+            None => "Nothing to undo".to_string(),
+        };
+        self.clamp_selection();
+    }
+    fn redo(&mut self){
+        self.message = match self.undo.redo(&mut self.habits, self.clock.today(), &self.vacations) {
+            Some(label) => format!("Redid: {}", label),
+            None => "Nothing to redo".to_string(),
+        };
+        self.clamp_selection();
+    }
+    // The TUI can stay open past the end of the day
+    fn catch_up(&mut self){
+        if self.clock.today() == self.day {
+            return;
+        }
+        self.day = self.clock.today();
+        for habit in self.habits.iter_mut() {
+            habit.roll_over(self.day);
+            habit.refresh_streak(self.day);
+        }
+    }
+    // Undo/redo can add, remove or archive habits under the cursor
+    fn clamp_selection(&mut self){
+        let order = order(&self.habits);
+        let selected = self.list_state.selected()
+            .filter(|i| order.contains(i))
+            .or(order.first().copied());
+        self.list_state.select(selected);
+    }
+}
 
-pub fn run() -> Result<(), Box<dyn std::error::Error>> {
+pub fn run(clock: &dyn Clock) -> Result<(), Box<dyn std::error::Error>> {
     // Part 2: Terminal setup (your code)
     enable_raw_mode()?;
     let mut stdout = io::stdout();
@@ -19,32 +120,76 @@ pub fn run() -> Result<(), Box<dyn std::error::Error>> {
     let backend =  CrosstermBackend::new(stdout);
     let mut terminal = Terminal::new(backend)?;
     // Part 3: Load habits (your code)
-    let mut habits: Vec<Habit> = match load_habits(){
-        Ok(h) => h,
-        Err(_) => vec![],
+    let config = load_config().unwrap_or_default();
+    clock.set_home(config.home());
+    let habits = load_habits(clock).unwrap_or_default();
+    let mut app = App{
+        undo: load_undo(&habits, clock).unwrap_or_default(),
+        habits,
+        list_state: ListState::default(),
+        clock,
+        day: clock.today(),
+        vacations: config.vacations,
+        message: String::new(),
     };
+    app.clamp_selection();
     // Part 4: Event loop (your code)
     loop{
+        app.catch_up();
         terminal.draw(|f| {
-            draw_ui(f, &habits);
-        })?;        let event = event::read()?;
-        match event {
-            Event::Key(key) => {
-                // Extract the KeyEvent
-                match key.code {
-                    KeyCode::Char('q') => break,
-                    _=>()
-                }
-            }
-            _=>()
+            draw_ui(f, &mut app);
+        })?;
+        // Wake up every second so running timers keep ticking
+        if !event::poll(Duration::from_secs(1))? {
+            continue;
+        }
+        let Event::Key(key) = event::read()? else { continue };
+        if key.kind != KeyEventKind::Press {
+            continue;
+        }
+        match key.code {
+            KeyCode::Char('q') => break,
+            KeyCode::Down | KeyCode::Char('j') => app.select_next(),
+            KeyCode::Up | KeyCode::Char('k') => app.select_previous(),
+            KeyCode::Char('c') => app.complete_selected(),
+            KeyCode::Char('t') => app.toggle_timer_selected(),
+            KeyCode::Char('r') if key.modifiers.contains(KeyModifiers::CONTROL) => app.redo(),
+            KeyCode::Char('u') => app.undo(),
+            _ => (),
         }
     }
     // Part 5: Cleanup (your code)
     disable_raw_mode()?;
     execute!(io::stdout(), LeaveAlternateScreen)?;    
+    save_habits(&app.habits)?;
+    save_undo(&app.undo)?;
     Ok(())
 }
 
+// Habits grouped under each of their tags, untagged ones last
+fn sections(habits: &[Habit]) -> Vec<(&str, Vec<usize>)>{
+    let mut tagged: BTreeMap<&str, Vec<usize>> = BTreeMap::new();
+    let mut untagged = Vec::new();
+    for (i, habit) in habits.iter().enumerate().filter(|(_, h)| !h.is_archived()) {
+        for tag in habit.tags() {
+            tagged.entry(tag.as_str()).or_default().push(i);
+        }
+        if habit.tags().is_empty() {
+            untagged.push(i);
+        }
+    }
+    let mut sections: Vec<(&str, Vec<usize>)> = tagged.into_iter().collect();
+    if !untagged.is_empty() {
+        sections.push(("untagged", untagged));
+    }
+    sections
+}
+
+// Habits in the order they are shown, each once even if it has several tags
+fn order(habits: &[Habit]) -> Vec<usize>{
+    sections(habits).into_iter().flat_map(|(_, habits)| habits).unique().collect()
+}
+
 // fn draw_ui(f: &mut ratatui::Frame, habits: &[Habit]) {
 //     // For now, just draw a simple message
 //     // We'll make it fancy later    
@@ -92,7 +237,7 @@ pub fn run() -> Result<(), Box<dyn std::error::Error>> {
 //     f.render_widget(paragraph_third, chunks[2]);
 // }
 
-fn draw_ui(f: &mut ratatui::Frame, habits: &[Habit]) {
+fn draw_ui(f: &mut ratatui::Frame, app: &mut App) {
     let chunks = Layout::default()
         .direction(Direction::Vertical)
         .constraints([
@@ -100,6 +245,7 @@ fn draw_ui(f: &mut ratatui::Frame, habits: &[Habit]) {
             Constraint::Min(0),
             Constraint::Length(1),
             Constraint::Length(1),
+            Constraint::Length(1),
         ])
         .split(f.area());
 
@@ -109,19 +255,73 @@ fn draw_ui(f: &mut ratatui::Frame, habits: &[Habit]) {
         .centered();
     f.render_widget(header, chunks[0]);
 
-    // Habit list (simplified - no progress bars yet)
-    let items: Vec<ListItem> = habits.iter()
-        .map(|h| ListItem::new(h.display_line()))
-        .collect();
-    
-    let list = List::new(items);
-    f.render_widget(list, chunks[1]);
+    // Habit list, grouped by tag once any habit has one
+    let sections = sections(&app.habits);
+    let show_headers = app.habits.iter().any(|h| !h.is_archived() && !h.tags().is_empty());
+    let mut items: Vec<ListItem> = Vec::new();
+    let mut selected_row = None;
+    for (tag, indices) in sections {
+        if show_headers {
+            items.push(ListItem::new(format!("── {} ──", tag)).bold());
+        }
+        for i in indices {
+            // A habit under several tags is highlighted the first time
+            if app.list_state.selected() == Some(i) && selected_row.is_none() {
+                selected_row = Some(items.len());
+            }
+            items.push(ListItem::new(app.habits[i].display_line(app.clock)));
+        }
+    }
+    // Selection is kept as a habit index, the widget wants a row
+    let mut rows = ListState::default().with_offset(app.list_state.offset()).with_selected(selected_row);
 
-    // Stats (comment out for now)
-    let stats_text = Paragraph::new("Stats temporarily disabled");
+    let body = Layout::default()
+        .direction(Direction::Horizontal)
+        .constraints([Constraint::Percentage(60), Constraint::Percentage(40)])
+        .split(chunks[1]);
+    let list = List::new(items).highlight_symbol("> ");
+    f.render_stateful_widget(list, body[0], &mut rows);
+    *app.list_state.offset_mut() = rows.offset();
+
+    // Detail pane for the selected habit
+    let details: Vec<ListItem> = app.list_state.selected()
+        .and_then(|i| app.habits.get(i))
+        .map(|h| h.details(app.clock).into_iter().map(ListItem::new).collect())
+        .unwrap_or_default();
+    let detail_pane = List::new(details).block(Block::default().borders(Borders::LEFT).title(" Details "));
+    f.render_widget(detail_pane, body[1]);
+
+    // Stats
+    let stats_text = Paragraph::new(StreakStats::calculate(&app.habits, app.clock.today()).display_stats());
     f.render_widget(stats_text, chunks[2]);
 
+    let message = Paragraph::new(app.message.as_str());
+    f.render_widget(message, chunks[3]);
+
     // Commands
-    let commands = Paragraph::new(" [q]uit");
-    f.render_widget(commands, chunks[3]);
-}
\ No newline at end of file
+    let commands = Paragraph::new(" [c]omplete  [t]imer  [u]ndo  [ctrl-r] redo  [q]uit");
+    f.render_widget(commands, chunks[4]);
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::clock::FixedClock;
+
+    #[test]
+    fn habits_show_under_every_tag_and_once_in_the_order(){
+        let clock = FixedClock::at("2026-01-03 09:00");
+        let mut habits = vec![
+            Habit::new_streak("read".to_string(), &clock),
+            Habit::new_streak("run".to_string(), &clock),
+            Habit::new_streak("nap".to_string(), &clock),
+            Habit::new_streak("old".to_string(), &clock),
+        ];
+        habits[0].tag(&["work"]).unwrap();
+        habits[1].tag(&["work", "health"]).unwrap();
+        habits[3].tag(&["health"]).unwrap();
+        habits[3].archive(&clock).unwrap();
+        assert_eq!(sections(&habits), [("health", vec![1]), ("work", vec![0, 1]), ("untagged", vec![2])]);
+        assert_eq!(order(&habits), [1, 0, 2]);
+    }
+}
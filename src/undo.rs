@@ -0,0 +1,160 @@
+use chrono::NaiveDate;
+use serde::{Deserialize, Serialize};
+
+use crate::clock::Clock;
+use crate::config::Vacation;
+use crate::habit::{adopt_ids, assign_ids, Habit};
+
+// How many steps back we remember
+const LIMIT: usize = 50;
+
+// The whole habit list as it was before a command ran
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Snapshot{
+    pub label: String,
+    pub habits: Vec<Habit>,
+}
+
+#[derive(Debug, Default, Serialize, Deserialize)]
+pub struct UndoStack{
+    undo: Vec<Snapshot>,
+    redo: Vec<Snapshot>,
+}
+
+impl UndoStack{
+    // Call after a command changed `habits`, passing the list from before it ran
+    pub fn record(&mut self, label: &str, before: Vec<Habit>){
+        self.undo.push(Snapshot { label: label.to_string(), habits: before });
+        if self.undo.len() > LIMIT {
+            self.undo.remove(0);
+        }
+        // A new change makes the undone ones unreachable
+        self.redo.clear();
+    }
+
+    // `vacations` are settings rather than history, so the current ones
+    // apply to whatever snapshot comes back
+    pub fn undo(&mut self, habits: &mut Vec<Habit>, today: NaiveDate, vacations: &[Vacation]) -> Option<String>{
+        let snapshot = self.undo.pop()?;
+        let current = std::mem::replace(habits, snapshot.habits);
+        catch_up(habits, today, vacations);
+        self.redo.push(Snapshot { label: snapshot.label.clone(), habits: current });
+        Some(snapshot.label)
+    }
+
+    pub fn redo(&mut self, habits: &mut Vec<Habit>, today: NaiveDate, vacations: &[Vacation]) -> Option<String>{
+        let snapshot = self.redo.pop()?;
+        let current = std::mem::replace(habits, snapshot.habits);
+        catch_up(habits, today, vacations);
+        self.undo.push(Snapshot { label: snapshot.label.clone(), habits: current });
+        Some(snapshot.label)
+    }
+
+    // Brings snapshots from older files up to the current format, with the
+    // same IDs as the habits in `loaded`
+    pub fn migrate(&mut self, loaded: &[Habit], clock: &dyn Clock){
+        for snapshot in self.undo.iter_mut().chain(self.redo.iter_mut()) {
+            for habit in snapshot.habits.iter_mut() {
+                habit.migrate(clock.today());
+            }
+            adopt_ids(&mut snapshot.habits, loaded);
+            assign_ids(&mut snapshot.habits, clock);
+        }
+    }
+
+    // Takes a habit out of every snapshot, so nothing can bring it back
+    pub fn forget(&mut self, id: &str){
+        for snapshot in self.undo.iter_mut().chain(self.redo.iter_mut()) {
+            snapshot.habits.retain(|h| h.id() != id);
+        }
+    }
+}
+
+// Snapshots are kept across sessions, so one can be from days ago
+fn catch_up(habits: &mut [Habit], today: NaiveDate, vacations: &[Vacation]){
+    for habit in habits {
+        habit.roll_over(today);
+        habit.apply_vacations(vacations, today);
+        habit.refresh_streak(today);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use chrono::TimeDelta;
+
+    use super::*;
+    use crate::clock::FixedClock;
+    use crate::habit::{Annotation, Excuse, HabitFunctions};
+
+    fn date(s: &str) -> NaiveDate{
+        s.parse().unwrap()
+    }
+
+    #[test]
+    fn undo_and_redo_bring_old_snapshots_up_to_today(){
+        let clock = FixedClock::at("2026-01-03 09:00");
+        let mut undo = UndoStack::default();
+        let mut habits = vec![Habit::new_quantity("water".to_string(), "ml".to_string(), 1.0, &clock)];
+        let before = habits.clone();
+        habits[0].log_amount(500.into(), Annotation::default(), &clock).unwrap();
+        undo.record("log water 500", before);
+        let before = habits.clone();
+        habits[0].log_amount(250.into(), Annotation::default(), &clock).unwrap();
+        undo.record("log water 250", before);
+        clock.advance(TimeDelta::days(2));
+        assert_eq!(undo.undo(&mut habits, clock.today(), &[]).as_deref(), Some("log water 250"));
+        let Habit::Quantity(q) = &habits[0] else { unreachable!() };
+        assert_eq!((q.log.today_date, q.log.today_total), (date("2026-01-05"), 0.0));
+        assert_eq!(q.log.history.iter().map(|e| e.value).collect::<Vec<_>>(), [500.0, 0.0]);
+        undo.redo(&mut habits, clock.today(), &[]).unwrap();
+        let Habit::Quantity(q) = &habits[0] else { unreachable!() };
+        assert_eq!(q.log.history.iter().map(|e| e.value).collect::<Vec<_>>(), [750.0, 0.0]);
+        assert!(undo.redo(&mut habits, clock.today(), &[]).is_none());
+    }
+
+    #[test]
+    fn purged_habits_cannot_be_undone_back(){
+        let clock = FixedClock::at("2026-01-03 09:00");
+        let mut undo = UndoStack::default();
+        let mut habits = vec![Habit::new_streak("read".to_string(), &clock), Habit::new_streak("run".to_string(), &clock)];
+        let before = habits.clone();
+        habits[1].complete(Annotation::default(), &clock).unwrap();
+        undo.record("complete run", before);
+        let purged = habits.remove(1);
+        undo.forget(purged.id());
+        undo.undo(&mut habits, clock.today(), &[]).unwrap();
+        assert_eq!(habits.iter().map(|h| h.name()).collect::<Vec<_>>(), ["read"]);
+        undo.redo(&mut habits, clock.today(), &[]).unwrap();
+        assert_eq!(habits.iter().map(|h| h.name()).collect::<Vec<_>>(), ["read"]);
+    }
+
+    #[test]
+    fn vacations_cover_undone_and_new_habits(){
+        let clock = FixedClock::at("2026-01-05 09:00");
+        let vacations = [Vacation { from: date("2026-01-01"), to: date("2026-01-03") }];
+        let mut undo = UndoStack::default();
+        let mut habits = vec![Habit::new_streak("read".to_string(), &clock)];
+        let before = habits.clone();
+        habits.push(Habit::new_streak("run".to_string(), &clock).on_vacations(&vacations, clock.today()));
+        undo.record("add run", before);
+        assert_eq!(habits[1].excused().and_then(|e| e.get(date("2026-01-02"))), Some(Excuse::Vacation));
+        undo.undo(&mut habits, clock.today(), &vacations).unwrap();
+        assert_eq!(habits[0].excused().and_then(|e| e.get(date("2026-01-02"))), Some(Excuse::Vacation));
+    }
+
+
+    #[test]
+    fn snapshots_from_before_ids_keep_the_loaded_ids(){
+        let clock = FixedClock::at("2026-01-03 09:00");
+        let loaded = vec![Habit::new_streak("read".to_string(), &clock)];
+        let json = r#"{"undo":[{"label":"add run","habits":[{"Streak":{"name":"read","streak":0}},{"Streak":{"name":"run","streak":0}}]}],"redo":[]}"#;
+        let mut undo: UndoStack = serde_json::from_str(json).unwrap();
+        undo.migrate(&loaded, &clock);
+        let mut habits = loaded.clone();
+        undo.undo(&mut habits, clock.today(), &[]).unwrap();
+        assert_eq!(habits[0].identity(), loaded[0].identity());
+        assert!(!habits[1].id().is_empty() && habits[1].id() != habits[0].id());
+        assert!(habits[1].identity().created.is_some());
+    }
+}
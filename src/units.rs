@@ -0,0 +1,184 @@
+use std::fmt;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Dimension{
+    Volume,
+    Distance,
+    Mass,
+    Duration,
+    Count,
+}
+
+impl fmt::Display for Dimension{
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result{
+        match self {
+            Dimension::Volume => write!(f, "volume"),
+            Dimension::Distance => write!(f, "distance"),
+            Dimension::Mass => write!(f, "mass"),
+            Dimension::Duration => write!(f, "duration"),
+            Dimension::Count => write!(f, "count"),
+        }
+    }
+}
+
+// A known unit and how many of its dimension's base unit (ml, m, g, s, x) it holds
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Unit{
+    pub symbol: &'static str,
+    pub dimension: Dimension,
+    pub size: f64,
+}
+
+const fn unit(symbol: &'static str, dimension: Dimension, size: f64) -> Unit{
+    Unit { symbol, dimension, size }
+}
+
+// Symbol, other spellings, and the unit. "oz" is both fluid and weight
+// ounces, which one is meant depends on the habit
+const UNITS: &[(&[&str], Unit)] = &[
+    (&["milliliter", "milliliters", "millilitre", "millilitres"], unit("ml", Dimension::Volume, 1.0)),
+    (&[], unit("cl", Dimension::Volume, 10.0)),
+    (&[], unit("dl", Dimension::Volume, 100.0)),
+    (&["liter", "liters", "litre", "litres"], unit("l", Dimension::Volume, 1000.0)),
+    (&["floz", "fl-oz"], unit("oz", Dimension::Volume, 29.5735)),
+    (&["cups"], unit("cup", Dimension::Volume, 236.588)),
+    (&["gallon", "gallons"], unit("gal", Dimension::Volume, 3785.41)),
+    (&["meter", "meters", "metre", "metres"], unit("m", Dimension::Distance, 1.0)),
+    (&[], unit("cm", Dimension::Distance, 0.01)),
+    (&["kilometer", "kilometers", "kilometre", "kilometres"], unit("km", Dimension::Distance, 1000.0)),
+    (&["mile", "miles"], unit("mi", Dimension::Distance, 1609.344)),
+    (&["foot", "feet"], unit("ft", Dimension::Distance, 0.3048)),
+    (&["yard", "yards"], unit("yd", Dimension::Distance, 0.9144)),
+    (&["gram", "grams"], unit("g", Dimension::Mass, 1.0)),
+    (&[], unit("mg", Dimension::Mass, 0.001)),
+    (&["kilogram", "kilograms", "kilo", "kilos"], unit("kg", Dimension::Mass, 1000.0)),
+    (&["ounce", "ounces"], unit("oz", Dimension::Mass, 28.3495)),
+    (&["lbs", "pound", "pounds"], unit("lb", Dimension::Mass, 453.592)),
+    (&["sec", "secs", "second", "seconds"], unit("s", Dimension::Duration, 1.0)),
+    (&["mins", "minute", "minutes"], unit("min", Dimension::Duration, 60.0)),
+    (&["hr", "hrs", "hour", "hours"], unit("h", Dimension::Duration, 3600.0)),
+    (&["times", "count", "reps"], unit("x", Dimension::Count, 1.0)),
+];
+
+// Finds a unit by any of its spellings, preferring one of `dimension`
+pub fn lookup(name: &str, dimension: Option<Dimension>) -> Option<Unit>{
+    let name = name.to_lowercase();
+    let mut matches = UNITS.iter()
+        .filter(|(aliases, u)| u.symbol == name || aliases.contains(&name.as_str()))
+        .map(|(_, u)| *u);
+    let first = matches.next()?;
+    Some(matches.chain(std::iter::once(first))
+        .find(|u| Some(u.dimension) == dimension)
+        .unwrap_or(first))
+}
+
+// `value` in `from` expressed in `to`. Units this module doesn't know only
+// convert to themselves
+pub fn convert(value: f64, from: &str, to: &str) -> Result<f64, String>{
+    if from.eq_ignore_ascii_case(to) {
+        return Ok(value);
+    }
+    // Whichever side is ambiguous takes the other side's dimension
+    let source = lookup(from, lookup(to, None).map(|u| u.dimension));
+    match (source, lookup(to, source.map(|u| u.dimension))) {
+        (Some(from_unit), Some(to_unit)) if from_unit.dimension == to_unit.dimension => {
+            Ok(value * from_unit.size / to_unit.size)
+        }
+        (Some(from_unit), Some(to_unit)) => {
+            Err(format!("Can't turn {} ({}) into {} ({})", from, from_unit.dimension, to, to_unit.dimension))
+        }
+        _ => Err(format!("Can't turn {} into {}", from, to)),
+    }
+}
+
+// A number as typed, with a unit if one was given: 500, 0.5l, 12 oz, -250ml
+#[derive(Debug, Clone, PartialEq)]
+pub struct Amount{
+    pub value: f64,
+    pub unit: Option<String>,
+}
+
+impl Amount{
+    pub fn parse(text: &str) -> Result<Amount, String>{
+        let text = text.trim();
+        // A leading minus takes an amount back
+        let start = usize::from(text.starts_with('-'));
+        let split = text[start..].find(|c: char| !(c.is_ascii_digit() || c == '.')).map_or(text.len(), |i| i + start);
+        let (number, unit) = text.split_at(split);
+        let value = number.parse::<f64>()
+            .map_err(|_| format!("'{}' is not an amount. Try 500, 0.5l or 12oz", text))?;
+        let unit = unit.trim();
+        Ok(Amount { value, unit: (!unit.is_empty()).then(|| unit.to_string()) })
+    }
+    // In a habit's unit. A bare number counts steps of `unit_size`
+    pub fn in_unit(&self, unit: &str, unit_size: f64) -> Result<f64, String>{
+        match &self.unit {
+            Some(from) => convert(self.value, from, unit),
+            None => Ok(self.value * unit_size),
+        }
+    }
+}
+
+impl From<u32> for Amount{
+    fn from(value: u32) -> Self{
+        Amount { value: value as f64, unit: None }
+    }
+}
+
+// Drops the float noise left by adding decimals, so 0.1 + 0.2 is 0.3
+pub fn round(value: f64) -> f64{
+    (value * 1000.0).round() / 1000.0
+}
+
+// Up to two decimals, without trailing zeros
+pub fn number(value: f64) -> String{
+    let text = format!("{:.2}", value);
+    text.trim_end_matches('0').trim_end_matches('.').to_string()
+}
+
+// `value` stored in `unit`, shown in `display` when it can be
+pub fn show(value: f64, unit: &str, display: Option<&str>) -> String{
+    match display.and_then(|d| convert(value, unit, d).ok().map(|v| (v, d))) {
+        Some((converted, display)) => format!("{}{}", number(converted), display),
+        None => format!("{}{}", number(value), unit),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn amounts_convert_to_the_habit_unit(){
+        let litres = Amount::parse("0.5l").unwrap();
+        assert_eq!(litres.in_unit("ml", 250.0).unwrap(), 500.0);
+        let ounces = Amount::parse("12oz").unwrap();
+        assert!((ounces.in_unit("ml", 1.0).unwrap() - 354.882).abs() < 0.001);
+        assert_eq!(Amount::parse("2").unwrap().in_unit("ml", 250.0).unwrap(), 500.0);
+        assert_eq!(Amount::parse("1.5 h").unwrap().in_unit("min", 1.0).unwrap(), 90.0);
+        assert_eq!(Amount::parse("-250ml").unwrap().in_unit("ml", 1.0).unwrap(), -250.0);
+    }
+
+    #[test]
+    fn ounces_follow_the_habit_dimension(){
+        assert!((convert(1.0, "oz", "g").unwrap() - 28.3495).abs() < 0.001);
+        assert!((convert(1.0, "oz", "ml").unwrap() - 29.5735).abs() < 0.001);
+        assert!((convert(100.0, "g", "oz").unwrap() - 3.5274).abs() < 0.001);
+        assert!((convert(1.0, "lb", "oz").unwrap() - 16.0).abs() < 0.001);
+    }
+
+    #[test]
+    fn mismatched_units_are_refused(){
+        assert!(convert(1.0, "km", "ml").is_err());
+        assert!(convert(1.0, "pages", "ml").is_err());
+        assert_eq!(convert(3.0, "pages", "pages").unwrap(), 3.0);
+        assert!(Amount::parse("lots").is_err());
+    }
+
+    #[test]
+    fn shows_in_the_preferred_unit(){
+        assert_eq!(show(1500.0, "ml", Some("l")), "1.5l");
+        assert_eq!(show(1500.0, "ml", None), "1500ml");
+        assert_eq!(show(1500.0, "pages", Some("l")), "1500pages");
+    }
+}
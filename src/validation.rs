@@ -13,6 +13,8 @@ pub fn is_valid_habit_name(name: &str) -> bool{
         && !name.ends_with('-')
 }
 
-pub fn find_habit_by_name(name: &str, habits: &[Habit]) -> Option<usize>{
-    habits.iter().position(|e|e.name() == name)
+// Looks a habit up by name, or failing that by ID
+pub fn find_habit(name_or_id: &str, habits: &[Habit]) -> Option<usize>{
+    habits.iter().position(|e| e.name() == name_or_id)
+        .or_else(|| habits.iter().position(|e| e.id() == name_or_id))
 }
\ No newline at end of file